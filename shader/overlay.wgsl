@@ -0,0 +1,40 @@
+// Text drawn over the show, e.g. the shader compilation errors
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Corners of the text in clip space: left, top, right, bottom
+@group(0) @binding(0)
+var<uniform> rect: vec4<f32>;
+@group(0) @binding(1)
+var text: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // Two triangles covering the rectangle
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let uv = corners[index];
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(mix(rect.xy, rect.zw, uv), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(text));
+    let texel = min(vec2<i32>(in.uv * size), vec2<i32>(size) - 1);
+    if (textureLoad(text, texel, 0).r > 0.5) {
+        return vec4<f32>(1.0, 0.4, 0.4, 1.0);
+    }
+    return vec4<f32>(0.0, 0.0, 0.0, 0.8);
+}
@@ -0,0 +1,35 @@
+// Region of the canvas rendered by the display, prepended to the shaders of the shows
+
+struct Viewport {
+	// Widths of the blended edges (left, top, right, bottom)
+	blend: vec4<f32>,
+	// Region of the canvas rendered by the display
+	offset: vec2<f32>,
+	size: vec2<f32>,
+	// Size of the display
+	resolution: vec2<f32>,
+	clip_scale: vec2<f32>,
+	clip_offset: vec2<f32>,
+}
+
+@group(0) @binding(6)
+var<uniform> viewport: Viewport;
+
+// Transforms a position from the clip space of the canvas to the one of the display
+fn crop(position: vec4<f32>) -> vec4<f32> {
+    return vec4<f32>(position.xy * viewport.clip_scale + viewport.clip_offset * position.w, position.zw);
+}
+
+// Position in the canvas of a pixel of the display
+fn canvas_position(position: vec2<f32>) -> vec2<f32> {
+    return viewport.offset + position * viewport.size / viewport.resolution;
+}
+
+// Attenuation of the edges overlapping another display, so that the overlapping projections
+// add up to the same brightness
+fn edge_blend(position: vec2<f32>) -> f32 {
+    let distance = vec4<f32>(position - viewport.offset, viewport.offset + viewport.size - position);
+    let ramp = smoothstep(vec4<f32>(0.0), vec4<f32>(1.0), distance / viewport.blend);
+    let blend = select(vec4<f32>(1.0), ramp, viewport.blend > vec4<f32>(0.0));
+    return blend.x * blend.y * blend.z * blend.w;
+}
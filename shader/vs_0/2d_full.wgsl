@@ -43,6 +43,7 @@ fn vs_main(
 		out.position.y = out.position.y * dims.x / dims.y;
 	}
 
+	out.position = crop(out.position);
 	out.color = instance.color;
 	return out;
 }
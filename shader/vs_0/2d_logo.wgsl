@@ -60,6 +60,7 @@ fn vs_main(
         out.text.y = 0.0;
     }
 
+    out.position = crop(out.position);
     return out;
 }
 
@@ -146,6 +146,7 @@ fn vs_main(
         out.position.y = out.position.y * dims.x / dims.y;
     }
 
+    out.position = crop(out.position);
     out.color = instance.color;
     out.noise_scale = instance.model_matrix_3.z;
     return out;
@@ -167,7 +168,7 @@ fn layered_noise(v: vec3<f32>, n_layers: i32) -> f32 {
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let n = layered_noise(vec3<f32>(in.position.xy * in.noise_scale, 0.05 * time), 6);
+    let n = layered_noise(vec3<f32>(canvas_position(in.position.xy) * in.noise_scale, 0.05 * time), 6);
     var v = sin(n * 60.0);
     return vec4<f32>(in.color.xyz * (0.5 + 0.5 * v), 1.0);
 }
@@ -149,6 +149,7 @@ fn vs_main(
 
     let p = vec3<f32>(out.position.x, out.position.y, time);
     out.position.x += layered_noise(p, 3) * 0.1;
+    out.position = crop(out.position);
 
     if (model.position.x == 0.0) && (model.position.x == 0.0) {
         out.color = vec4<f32>(0.0, 0.0, 0.0, 0.0);
@@ -207,7 +207,7 @@ fn vs_main(
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let pos: vec2<f32> = (in.position.xy / vec2<f32>(dimensions.xy) * 2. - 1.) - in.pos;
+    let pos: vec2<f32> = (canvas_position(in.position.xy) / vec2<f32>(dimensions.xy) * 2. - 1.) - in.pos;
     let asp = f32(dimensions.x) / f32(dimensions.y);
     var rd: vec3<f32> = normalize(vec3<f32>(asp * pos.x, pos.y, -1.5));
     var ro: vec3<f32> = vec3<f32>(0., 0., 4.5);
@@ -97,13 +97,26 @@ fn snoise(v: vec3<f32>) -> f32 {
     return 105.0 * dot(m * m, vec4(dot(p0, x0), dot(p1, x1), dot(p2, x2), dot(p3, x3)));
 }
 
+struct Band {
+	gain: f32,
+	level: f32,
+	envelope: f32,
+	peak: f32,
+}
+
 struct Audio {
-	gain: vec3<f32>,
-	_offset: f32,
+	nb_bands: u32,
+	onset: u32,
+	silence: u32,
+	bpm: f32,
+	beat_phase: f32,
+	beat_count: u32,
+	frame: u32,
+	bands: array<Band>,
 }
 
 @group(0) @binding(0)
-var<uniform> audio: Audio;
+var<storage, read> audio: Audio;
 
 @group(0) @binding(1)
 var<uniform> time: f32;
@@ -111,6 +124,16 @@ var<uniform> time: f32;
 @group(0) @binding(2)
 var<uniform> dimensions: vec2<u32>;
 
+struct Params {
+	speed: f32,
+	duration: f32,
+	intensity: f32,
+	hue: f32,
+}
+
+@group(0) @binding(5)
+var<uniform> params: Params;
+
 @group(1) @binding(0)
 var t_framebuffer: texture_2d<f32>;
 @group(1) @binding(1)
@@ -147,15 +170,29 @@ fn layered_noise(v: vec3<f32>, n_layers: i32) -> f32 {
     return n;
 }
 
+// Rotates the hue of a color by angle radians around the grey axis
+fn rotate_hue(color: vec3<f32>, angle: f32) -> vec3<f32> {
+    let k = vec3<f32>(0.57735027, 0.57735027, 0.57735027);
+    let c = cos(angle);
+    return color * c + cross(k, color) * sin(angle) + k * dot(k, color) * (1.0 - c);
+}
+
+// Gain of the i-th of n groups of bands
+fn band_gain(i: u32, n: u32) -> f32 {
+    return audio.bands[i * audio.nb_bands / n].gain;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let noise = layered_noise(vec3<f32>(in.position.xy / vec2<f32>(dimensions) * 2.0, time), 4);
-    let offset = (10.0 + 5.0 * cos(time * 0.0034 * exp(audio.gain))) * noise * exp(audio.gain);
-    let red = textureSample(t_framebuffer, s_framebuffer, (in.position.xy + vec2<f32>(offset.x, 0.0)) / vec2<f32>(dimensions));
-    let green = textureSample(t_framebuffer, s_framebuffer, (in.position.xy + vec2<f32>(offset.y, 0.0)) / vec2<f32>(dimensions));
-    let blue = textureSample(t_framebuffer, s_framebuffer, (in.position.xy + vec2<f32>(offset.z, 0.0)) / vec2<f32>(dimensions));
+    let position = canvas_position(in.position.xy);
+    let noise = layered_noise(vec3<f32>(position / vec2<f32>(dimensions) * 2.0, time), 4);
+    let gain = vec3<f32>(band_gain(0u, 3u), band_gain(1u, 3u), band_gain(2u, 3u));
+    let offset = 2.0 * params.intensity * (10.0 + 5.0 * cos(time * 0.0034 * exp(gain))) * noise * exp(gain);
+    let red = textureSample(t_framebuffer, s_framebuffer, (in.position.xy + vec2<f32>(offset.x, 0.0)) / viewport.resolution);
+    let green = textureSample(t_framebuffer, s_framebuffer, (in.position.xy + vec2<f32>(offset.y, 0.0)) / viewport.resolution);
+    let blue = textureSample(t_framebuffer, s_framebuffer, (in.position.xy + vec2<f32>(offset.z, 0.0)) / viewport.resolution);
     var out = red;
     out.y = green.y;
     out.z = blue.z;
-    return out;
+    return vec4<f32>(rotate_hue(out.xyz, 6.2831853 * (params.hue - 0.5)) * edge_blend(position), out.w);
 }
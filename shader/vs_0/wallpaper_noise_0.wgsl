@@ -69,9 +69,10 @@ fn vs_main(
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let position = canvas_position(in.position.xy);
     let min = f32(min(dimensions.x, dimensions.y));
-    let x = (in.position.x - f32(dimensions.x) / 2.0) / min;
-    let y = (in.position.y - f32(dimensions.y) / 2.0) / min;
+    let x = (position.x - f32(dimensions.x) / 2.0) / min;
+    let y = (position.y - f32(dimensions.y) / 2.0) / min;
     let v = kbmarcher(vec3<f32>(x, y, time));
     return vec4<f32>(in.color.xyz * (0.5 + 0.5 * v), 1.0);
 }
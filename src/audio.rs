@@ -1,156 +1,768 @@
+mod beat;
+pub mod clock;
+mod envelope;
+mod file;
+mod gate;
+mod input;
+pub mod link;
+pub mod meter;
+
+use crate::config::{BandConfig, EnvelopeConfig};
+use beat::{BeatTracker, OnsetDetector};
+use clock::ClockReceiver;
 use core::f32::consts::PI;
-use cpal::{
-    platform::Stream,
-    traits::{DeviceTrait, HostTrait, StreamTrait},
-    FromSample, Sample,
-};
-use crossterm::{cursor, terminal, ExecutableCommand};
-use promptly::prompt_default;
+use cpal::{platform::Stream, FromSample, Sample};
+use envelope::Envelope;
+use file::FileSource;
+use gate::Gate;
+use input::InputSource;
+use meter::Meter;
 use realfft::{num_complex::Complex, RealFftPlanner, RealToComplex};
+use rtrb::{Consumer, Producer, RingBuffer};
 use std::collections::VecDeque;
 use std::error::Error;
-use std::io::{stdout, Write};
-use std::sync::{Arc, Mutex};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+pub use input::list_devices;
+
+// Number of log-frequency bins of the published spectrum
+pub const SPECTRUM_SIZE: usize = 512;
+// Number of published time-domain samples
+pub const WAVEFORM_SIZE: usize = 512;
+// Dynamic range of the published spectrum, in dB
+const SPECTRUM_RANGE: f32 = 90.0;
+// Noise floor of the bands without noise profile, in dB
+const DEFAULT_NOISE_FLOOR: f32 = -50.0;
+// Percentile of the band levels measured by the calibration taken as noise floor
+const NOISE_FLOOR_PERCENTILE: f32 = 0.95;
+// Time between two polls of the analysed frames during the calibration
+const CALIBRATION_POLL_PERIOD: Duration = Duration::from_millis(50);
+// Number of analysed frames waiting for the meter
+const METER_CAPACITY: usize = 64;
+// Number of analysed frames waiting for the render thread
+const HISTORY_CAPACITY: usize = 64;
+// Number of commands waiting for the audio thread
+const COMMAND_CAPACITY: usize = 16;
+// Longest statistics window, in seconds
+const MAX_STAT_WINDOW: f32 = 3600.0;
 
-pub const NB_AUDIO_CHANNELS: usize = 3;
-const STAT_WINDOW_DURATION: usize = 5; // In seconds
+#[derive(Clone, Debug)]
+pub struct Data {
+    pub gain: Vec<f32>,
+    // Band levels, between 0 (-SPECTRUM_RANGE dB) and 1 (0 dB)
+    pub level: Vec<f32>,
+    // Envelopes of the band levels
+    pub envelope: Vec<f32>,
+    // Held peaks of the envelopes
+    pub peak: Vec<f32>,
+    // Magnitude spectrum, between 0 (-SPECTRUM_RANGE dB) and 1 (0 dB)
+    pub spectrum: Vec<f32>,
+    // Last samples of the analysed signal
+    pub waveform: Vec<f32>,
+    // Number of analysed chunks
+    pub frame: u32,
+    // The last chunk is an onset
+    pub onset: bool,
+    // The input is below the noise floor
+    pub silence: bool,
+    pub bpm: f32,
+    // Position in the current beat, between 0 and 1
+    pub beat_phase: f32,
+    pub beat_count: u32,
+}
 
+// Header of the audio storage buffer, followed by the bands
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Data {
-    pub gain: [f32; NB_AUDIO_CHANNELS],
-    _offset: f32,
+struct DataHeader {
+    nb_bands: u32,
+    onset: u32,
+    silence: u32,
+    bpm: f32,
+    beat_phase: f32,
+    beat_count: u32,
+    frame: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BandData {
+    gain: f32,
+    level: f32,
+    envelope: f32,
+    peak: f32,
 }
 
 impl Data {
-    pub fn new() -> Data {
+    pub fn new(nb_bands: usize) -> Data {
         Data {
-            gain: [0.0; NB_AUDIO_CHANNELS],
-            _offset: 0.0,
+            gain: vec![0.0; nb_bands],
+            level: vec![0.0; nb_bands],
+            envelope: vec![0.0; nb_bands],
+            peak: vec![0.0; nb_bands],
+            spectrum: vec![0.0; SPECTRUM_SIZE],
+            waveform: vec![0.0; WAVEFORM_SIZE],
+            frame: 0,
+            onset: false,
+            silence: true,
+            bpm: 0.0,
+            beat_phase: 0.0,
+            beat_count: 0,
         }
     }
+
+    // Copies the frame without allocating, the frames having the same number of bands
+    fn copy_from(&mut self, other: &Data) {
+        self.gain.clone_from(&other.gain);
+        self.level.clone_from(&other.level);
+        self.envelope.clone_from(&other.envelope);
+        self.peak.clone_from(&other.peak);
+        self.spectrum.clone_from(&other.spectrum);
+        self.waveform.clone_from(&other.waveform);
+        self.frame = other.frame;
+        self.onset = other.onset;
+        self.silence = other.silence;
+        self.bpm = other.bpm;
+        self.beat_phase = other.beat_phase;
+        self.beat_count = other.beat_count;
+    }
+
+    // Content of the audio storage buffer
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header = DataHeader {
+            nb_bands: self.gain.len() as u32,
+            onset: self.onset as u32,
+            silence: self.silence as u32,
+            bpm: self.bpm,
+            beat_phase: self.beat_phase,
+            beat_count: self.beat_count,
+            frame: self.frame,
+        };
+        let bands: Vec<_> = (0..self.gain.len())
+            .map(|i| BandData {
+                gain: self.gain[i],
+                level: self.level[i],
+                envelope: self.envelope[i],
+                peak: self.peak[i],
+            })
+            .collect();
+        let mut bytes = Vec::with_capacity(
+            std::mem::size_of::<DataHeader>() + std::mem::size_of::<BandData>() * bands.len(),
+        );
+        bytes.extend_from_slice(bytemuck::bytes_of(&header));
+        bytes.extend_from_slice(bytemuck::cast_slice(&bands));
+        bytes
+    }
 }
 
-struct Buffer {
+// Signal analysed separately, made of one or several input channels
+struct Signal {
+    // Last samples, the oldest first
+    samples: Vec<f32>,
+    // Windowed samples
     input: Vec<f32>,
     output: Vec<Complex<f32>>,
+    mean: Vec<f32>,
+    var: Vec<f32>,
+    stat_window: Vec<VecDeque<f32>>,
+    // Smoothed gain of each band, NaN when undefined (silence or reset)
+    gain: Vec<f32>,
+    envelopes: Vec<Envelope>,
+    gate: Gate,
+}
+
+struct Band {
+    // Range of frequency bins
+    start: usize,
+    end: usize,
+    // Weighting of the gain
+    gain: f32,
+    smoothing: f32,
+    // Number of frames of the statistics window
+    stat_window_size: usize,
+    envelope: Envelope,
+}
+
+struct Buffer {
+    signals: Vec<Signal>,
     scratch: Vec<Complex<f32>>,
     window: Vec<f32>,
+    // Magnitude of a full scale sine
+    reference: f32,
     pos: usize,
     len: usize,
+    // Number of new samples between two analysed frames
+    hop: usize,
     r2c: Arc<dyn RealToComplex<f32>>,
-    mean: Vec<f32>,
-    var: Vec<f32>,
+    nb_channels: usize,
+    channel_mode: ChannelMode,
     count: u64,
-    index_limits: Vec<usize>,
-    stat_window: Vec<VecDeque<f32>>,
-    stat_window_size: usize,
+    bands: Vec<Band>,
+    // Range of frequency bins used by the onset detection
+    magnitude_range: (usize, usize),
+    spectrum_limits: Vec<(usize, usize)>,
+    // Ring buffer of the last samples of the first signal
+    waveform: Vec<f32>,
+    waveform_pos: usize,
+    // Analysed frame and scratch buffers, allocated once so that the audio thread never allocates
+    data: Data,
+    levels: Vec<f32>,
+    db_levels: Vec<f32>,
+    magnitudes: Vec<f32>,
+    total_magnitudes: Vec<f32>,
+    onset_detector: OnsetDetector,
+    beat_tracker: BeatTracker,
+    // External clock followed instead of the beat tracking
+    clock: Option<ClockReceiver>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum ChannelMode {
+    // Analyse a single input channel
+    Select(usize),
+    // Analyse the mean of all the input channels
+    Mix,
+    // Analyse each pair of input channels separately, each pair having its own bands
+    Pairs,
+}
+
+impl FromStr for ChannelMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mix" => Ok(ChannelMode::Mix),
+            "pairs" => Ok(ChannelMode::Pairs),
+            _ => s.parse().map(ChannelMode::Select).map_err(|_| {
+                format!("invalid channel mode {s} (expected a channel id, mix or pairs)")
+            }),
+        }
+    }
+}
+
+impl ChannelMode {
+    fn nb_signals(&self, nb_channels: usize) -> usize {
+        match self {
+            ChannelMode::Select(_) | ChannelMode::Mix => 1,
+            ChannelMode::Pairs => nb_channels.div_ceil(2),
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum WindowFunction {
+    #[default]
+    Hann,
+    Hamming,
+    BlackmanHarris,
+}
+
+impl WindowFunction {
+    fn coefficients(&self, len: usize) -> Vec<f32> {
+        let n = (len - 1) as f32;
+        (0..len)
+            .map(|i| {
+                let x = 2.0 * PI * i as f32 / n;
+                match self {
+                    WindowFunction::Hann => 0.5 * (1.0 - x.cos()),
+                    WindowFunction::Hamming => 0.54 - 0.46 * x.cos(),
+                    WindowFunction::BlackmanHarris => {
+                        0.35875 - 0.48829 * x.cos() + 0.14128 * (2.0 * x).cos()
+                            - 0.01168 * (3.0 * x).cos()
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+type AudioOut = Result<(Receiver, SourceHandle), Box<dyn Error>>;
+
+#[derive(Clone, Debug)]
+pub struct AudioConfig {
+    // Name of the cpal host (e.g. alsa, jack)
+    pub host: Option<String>,
+    // Index of the input device or a part of its name
+    pub device: Option<String>,
+    // Audio file (WAV or FLAC) used instead of the input device
+    pub file: Option<PathBuf>,
+    // Playback speed of the audio file, 0 to analyse it as fast as possible
+    pub speed: f32,
+    // Play the audio file on the default output device
+    pub playback: bool,
+    // Input channels that are analysed
+    pub channel_mode: ChannelMode,
+    // Number of frequency bands, split into equal octaves when no band is declared
+    pub nb_bands: usize,
+    pub bands: Vec<BandConfig>,
+    pub envelope: EnvelopeConfig,
+    // Default duration of the statistics window of the bands, in seconds
+    pub stat_window: f32,
+    // Noise floor of each band of each signal, in dB
+    pub noise_floors: Option<Vec<f32>>,
+    // Number of samples of each analysed frame
+    pub fft_size: usize,
+    // Number of samples between two analysed frames, the frames overlap when it is smaller
+    // than fft_size
+    pub hop_size: usize,
+    pub window: WindowFunction,
+    // Analysed frequency range, in Hz
+    pub min_freq: u32,
+    pub max_freq: u32,
+    // Consumer of the analysed frames
+    pub meter: meter::MeterKind,
+}
+
+pub trait AudioSource {
+    fn sample_rate(&self) -> u32;
+
+    fn nb_channels(&self) -> u16;
+
+    // Starts feeding the samples to the analyzer
+    fn start(self: Box<Self>, analyzer: Analyzer) -> Result<SourceHandle, Box<dyn Error>>;
+}
+
+// Keeps the audio source running while it is alive
+pub struct SourceHandle {
+    _stream: Option<Stream>,
+    _thread: Option<JoinHandle<()>>,
+}
+
+impl SourceHandle {
+    fn from_stream(stream: Stream) -> Self {
+        SourceHandle {
+            _stream: Some(stream),
+            _thread: None,
+        }
+    }
+
+    fn from_thread(thread: JoinHandle<()>) -> Self {
+        SourceHandle {
+            _stream: None,
+            _thread: Some(thread),
+        }
+    }
+}
+
+// Ring of analysed frames, the frames being taken from a pool of preallocated frames to which
+// the consumer returns them
+struct FrameRing {
+    ring: Producer<Data>,
+    pool: Consumer<Data>,
+}
+
+impl FrameRing {
+    // Creates the ring, its consumer and the producer returning the frames to the pool
+    fn new(capacity: usize, nb_gains: usize) -> (Self, Consumer<Data>, Producer<Data>) {
+        let (ring, consumer) = RingBuffer::new(capacity);
+        let (mut pool_producer, pool) = RingBuffer::new(capacity);
+        for _ in 0..capacity {
+            let _ = pool_producer.push(Data::new(nb_gains));
+        }
+        (FrameRing { ring, pool }, consumer, pool_producer)
+    }
+
+    // The frame is dropped when the ring is full
+    fn push(&mut self, data: &Data) {
+        // Only this thread pushes to the ring, so the push cannot fail after the check
+        if self.ring.slots() > 0 {
+            if let Ok(mut frame) = self.pool.pop() {
+                frame.copy_from(data);
+                let _ = self.ring.push(frame);
+            }
+        }
+    }
 }
 
-type AudioOut = Result<(Arc<Mutex<Data>>, Stream), Box<dyn Error>>;
+// Sending end of the analysed frames, on the audio thread
+struct Sender {
+    latest: triple_buffer::Input<Data>,
+    history: FrameRing,
+    meter: FrameRing,
+}
 
-pub fn init(chunck_size: u32, min_freq: u32, max_freq: u32) -> AudioOut {
-    /*
-    // For debugging
-    let hosts = cpal::available_hosts();
-    for h in hosts {
-        println!("{:?}", h);
+impl Sender {
+    // Never blocks nor allocates
+    fn send(&mut self, data: &Data) {
+        self.meter.push(data);
+        self.history.push(data);
+        self.latest.input_buffer().copy_from(data);
+        self.latest.publish();
     }
-    */
+}
 
-    let host = cpal::default_host();
-    let devices: Vec<_> = host.input_devices()?.collect();
+// Commands handled by the audio thread
+#[derive(Clone, Copy, Debug)]
+pub enum Command {
+    // Clear the statistics of the bands
+    Reset,
+}
+
+// Receiving end of the analysed frames, on the render thread
+pub struct Receiver {
+    latest: triple_buffer::Output<Data>,
+    history: Consumer<Data>,
+    // Returns the frames of the history to the audio thread
+    history_pool: Producer<Data>,
+    commands: Producer<Command>,
+}
 
-    for (i, d) in devices.iter().enumerate() {
-        println!("[DEVICE {}] {}", i, d.name()?);
+impl Receiver {
+    // Logs the command here, the audio thread handling it without I/O
+    pub fn send(&mut self, command: Command) {
+        if self.commands.push(command).is_err() {
+            log::warn!("The audio thread is not responding, {:?} dropped", command);
+            return;
+        }
+        match command {
+            Command::Reset => log::info!("Audio statistics reset"),
+        }
     }
 
-    let device_id: usize = prompt_default("Select Device Id", 0)?;
+    // Last analysed frame
+    pub fn latest(&mut self) -> &Data {
+        self.latest.read()
+    }
 
-    let device = &devices[device_id];
+    // Frames analysed since the previous call, oldest first
+    pub fn new_frames(&mut self) -> Vec<Data> {
+        let mut frames = vec![];
+        while let Ok(data) = self.history.pop() {
+            frames.push(data.clone());
+            let _ = self.history_pool.push(data);
+        }
 
-    let config = device.default_input_config()?;
+        // The frames that did not fit in the history are skipped up to the last one
+        if self.latest.update() {
+            let latest = self.latest.output_buffer();
+            if frames.last().is_none_or(|d| d.frame < latest.frame) {
+                frames.push(latest.clone());
+            }
+        }
+        frames
+    }
+}
 
-    println!("[DEFAULT AUDIO CONFIG] {:?}", config);
+pub struct Analyzer {
+    buffer: Buffer,
+    sender: Sender,
+    commands: Consumer<Command>,
+}
+
+impl Analyzer {
+    pub fn process<T>(&mut self, input: &[T])
+    where
+        T: Sample,
+        f32: FromSample<T>,
+    {
+        while let Ok(command) = self.commands.pop() {
+            match command {
+                Command::Reset => reset(&mut self.buffer),
+            }
+        }
+        handle_input(input, &mut self.buffer, &mut self.sender);
+    }
+}
+
+pub fn init(
+    audio_config: &AudioConfig,
+    meter: Box<dyn Meter>,
+    clock: Option<ClockReceiver>,
+) -> AudioOut {
+    let source: Box<dyn AudioSource> = match &audio_config.file {
+        Some(path) => Box::new(FileSource::new(
+            path,
+            audio_config.speed,
+            audio_config.playback,
+        )?),
+        None => Box::new(InputSource::new(audio_config)?),
+    };
+
+    let (analyzer, receiver) = analyzer(
+        audio_config,
+        source.sample_rate(),
+        source.nb_channels() as usize,
+        meter,
+        clock,
+    )?;
+    let handle = source.start(analyzer)?;
+    Ok((receiver, handle))
+}
+
+// Creates the analyzer of the samples of a source and the receiving end of the analysed frames
+fn analyzer(
+    audio_config: &AudioConfig,
+    sample_rate: u32,
+    nb_channels: usize,
+    meter: Box<dyn Meter>,
+    clock: Option<ClockReceiver>,
+) -> Result<(Analyzer, Receiver), Box<dyn Error>> {
+    let channel_mode = audio_config.channel_mode;
+    if let ChannelMode::Select(c) = channel_mode {
+        if c >= nb_channels {
+            return Err(format!(
+                "Audio channel {} is not available, the input has {} channels",
+                c, nb_channels
+            )
+            .into());
+        }
+    }
+
+    let fft_size = audio_config.fft_size;
+    let hop_size = audio_config.hop_size;
+    let min_freq = audio_config.min_freq;
+    let nyquist = sample_rate / 2;
+    let max_freq = if audio_config.max_freq > nyquist {
+        log::warn!(
+            "The highest audio frequency is limited to {} Hz by the sample rate",
+            nyquist
+        );
+        nyquist
+    } else {
+        audio_config.max_freq
+    };
+    if fft_size < 2 {
+        return Err(format!("Invalid FFT size {}", fft_size).into());
+    }
+    if hop_size == 0 || hop_size > fft_size {
+        return Err(format!(
+            "Invalid hop size {}, expected a value between 1 and the FFT size {}",
+            hop_size, fft_size
+        )
+        .into());
+    }
+    if min_freq == 0 || min_freq >= max_freq {
+        return Err(format!("Invalid frequency range {}-{} Hz", min_freq, max_freq).into());
+    }
 
     let mut real_planner = RealFftPlanner::<f32>::new();
-    let r2c = real_planner.plan_fft_forward(chunck_size as usize);
-    let input = r2c.make_input_vec();
-    let output = r2c.make_output_vec();
+    let r2c = real_planner.plan_fft_forward(fft_size);
     let scratch = r2c.make_scratch_vec();
-    let stat_window = vec![VecDeque::new(); NB_AUDIO_CHANNELS];
-    let hanning_window = (0..input.len())
-        .map(|i| 0.5 * (1.0 - ((2.0 * PI * i as f32) / (input.len() - 1) as f32).cos()))
-        .collect();
+    let frame_rate = sample_rate as f32 / hop_size as f32;
+    let window = audio_config.window.coefficients(fft_size);
+    let stat_window_size =
+        calculate_stat_window(audio_config.stat_window, frame_rate).ok_or_else(|| {
+            format!(
+                "Invalid statistics window of {} s, expected a duration between an audio frame and {} s",
+                audio_config.stat_window, MAX_STAT_WINDOW
+            )
+        })?;
+    let bands = if audio_config.bands.is_empty() {
+        let index_limits = calculate_channel_index(
+            min_freq,
+            max_freq,
+            audio_config.nb_bands as u32,
+            sample_rate,
+            fft_size,
+        );
+        if index_limits[audio_config.nb_bands] > fft_size / 2 + 1 {
+            return Err(format!(
+                "Too many audio bands ({}) for the FFT size {} and the frequency range {}-{} Hz",
+                audio_config.nb_bands, fft_size, min_freq, max_freq
+            )
+            .into());
+        }
+        let envelope = calculate_envelope(None, &audio_config.envelope, frame_rate)?;
+        index_limits
+            .windows(2)
+            .map(|l| Band {
+                start: l[0],
+                end: l[1],
+                gain: 1.0,
+                smoothing: 0.0,
+                stat_window_size,
+                envelope: envelope.clone(),
+            })
+            .collect()
+    } else {
+        audio_config
+            .bands
+            .iter()
+            .map(|b| calculate_band(b, audio_config, sample_rate, frame_rate))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    let nb_bands = bands.len();
+    let magnitude_range = (
+        bands.iter().map(|b| b.start).min().unwrap(),
+        bands.iter().map(|b| b.end).max().unwrap(),
+    );
 
-    let sample_rate = config.sample_rate().0;
-    let mut buffer = Buffer {
-        input,
-        output,
+    let nb_signals = channel_mode.nb_signals(nb_channels);
+    let noise_floors = match &audio_config.noise_floors {
+        Some(floors) if floors.len() != nb_signals * nb_bands => {
+            return Err(format!(
+                "The noise profile has {} bands while {} bands are analysed, calibrate it again",
+                floors.len(),
+                nb_signals * nb_bands
+            )
+            .into());
+        }
+        Some(floors) => floors.clone(),
+        None => vec![DEFAULT_NOISE_FLOOR; nb_signals * nb_bands],
+    };
+    let signals = (0..nb_signals)
+        .map(|s| Signal {
+            samples: vec![0.0; fft_size],
+            input: r2c.make_input_vec(),
+            output: r2c.make_output_vec(),
+            mean: vec![0.0; nb_bands],
+            var: vec![0.0; nb_bands],
+            stat_window: bands
+                .iter()
+                .map(|b| VecDeque::with_capacity(b.stat_window_size))
+                .collect(),
+            gain: vec![f32::NAN; nb_bands],
+            envelopes: bands.iter().map(|b| b.envelope.clone()).collect(),
+            gate: Gate::new(&noise_floors[s * nb_bands..(s + 1) * nb_bands], frame_rate),
+        })
+        .collect();
+    let spectrum_limits = calculate_spectrum_index(min_freq, max_freq, sample_rate, fft_size);
+    let onset_detector = OnsetDetector::new(
+        nb_signals * (magnitude_range.1 - magnitude_range.0),
+        frame_rate,
+    );
+    let buffer = Buffer {
+        signals,
         scratch,
-        len: chunck_size as usize,
+        len: fft_size,
+        hop: hop_size,
         pos: 0,
         r2c,
-        mean: vec![0.0; NB_AUDIO_CHANNELS],
-        var: vec![0.0; NB_AUDIO_CHANNELS],
+        nb_channels,
+        channel_mode,
         count: 0,
-        window: hanning_window,
-        index_limits: calculate_channel_index(
-            min_freq,
-            max_freq,
-            NB_AUDIO_CHANNELS as u32,
-            sample_rate,
-            chunck_size as usize,
-        ),
-        stat_window,
-        stat_window_size: sample_rate as usize * STAT_WINDOW_DURATION / chunck_size as usize,
+        reference: window.iter().sum::<f32>() / 2.0,
+        window,
+        bands,
+        magnitude_range,
+        spectrum_limits,
+        waveform: vec![0.0; WAVEFORM_SIZE],
+        waveform_pos: 0,
+        // Each signal has its own bands
+        data: Data::new(nb_signals * nb_bands),
+        levels: vec![0.0; nb_bands],
+        db_levels: vec![0.0; nb_bands],
+        magnitudes: Vec::with_capacity(nb_signals * (magnitude_range.1 - magnitude_range.0)),
+        total_magnitudes: vec![0.0; fft_size / 2 + 1],
+        onset_detector,
+        beat_tracker: BeatTracker::new(frame_rate),
+        clock,
     };
 
-    let err_fn = move |err| {
-        eprintln!("an error occurred on stream: {}", err);
+    let nb_gains = nb_signals * nb_bands;
+    let (latest_input, latest_output) = triple_buffer::triple_buffer(&Data::new(nb_gains));
+    let (history, history_consumer, history_pool) = FrameRing::new(HISTORY_CAPACITY, nb_gains);
+    let (meter_ring, meter_consumer, meter_pool) = FrameRing::new(METER_CAPACITY, nb_gains);
+    let (command_producer, command_consumer) = RingBuffer::new(COMMAND_CAPACITY);
+    let analyzer = Analyzer {
+        buffer,
+        sender: Sender {
+            latest: latest_input,
+            history,
+            meter: meter_ring,
+        },
+        commands: command_consumer,
     };
-
-    let audio_data = Data {
-        gain: [0.0; NB_AUDIO_CHANNELS],
-        _offset: 0.0,
+    let receiver = Receiver {
+        latest: latest_output,
+        history: history_consumer,
+        history_pool,
+        commands: command_producer,
     };
-    let audio_data_arc = Arc::new(Mutex::new(audio_data));
-    let audio_data_arc1 = audio_data_arc.clone();
-
-    let stream = match config.sample_format() {
-        cpal::SampleFormat::I8 => device.build_input_stream(
-            &config.into(),
-            move |data, _: &_| handle_input::<i8>(data, &mut buffer, &audio_data_arc1),
-            err_fn,
-            None,
-        )?,
-        cpal::SampleFormat::I16 => device.build_input_stream(
-            &config.into(),
-            move |data, _: &_| handle_input::<i16>(data, &mut buffer, &audio_data_arc1),
-            err_fn,
-            None,
-        )?,
-        cpal::SampleFormat::I32 => device.build_input_stream(
-            &config.into(),
-            move |data, _: &_| handle_input::<i32>(data, &mut buffer, &audio_data_arc1),
-            err_fn,
-            None,
-        )?,
-        cpal::SampleFormat::F32 => device.build_input_stream(
-            &config.into(),
-            move |data, _: &_| handle_input::<f32>(data, &mut buffer, &audio_data_arc1),
-            err_fn,
+
+    // The meter stops with the analyzer
+    meter::spawn(meter_consumer, meter_pool, meter);
+
+    Ok((analyzer, receiver))
+}
+
+// Audio file analysed in lockstep with the rendered frames instead of in real time
+pub struct Offline {
+    source: FileSource,
+    analyzer: Analyzer,
+    receiver: Receiver,
+    // Samples read from the file at each frame
+    block: Vec<f32>,
+    // Number of frames of samples analysed so far
+    position: usize,
+}
+
+impl Offline {
+    pub fn new(audio_config: &AudioConfig) -> Result<Self, Box<dyn Error>> {
+        let path = audio_config
+            .file
+            .as_ref()
+            .ok_or("No audio file to render")?;
+        let source = FileSource::new(path, 0.0, false)?;
+        let (analyzer, receiver) = analyzer(
+            audio_config,
+            source.sample_rate(),
+            source.nb_channels() as usize,
+            Box::new(meter::SilentMeter),
             None,
-        )?,
-        _ => return Err(Box::from("Unsupported sample format")),
-    };
+        )?;
+        Ok(Offline {
+            source,
+            analyzer,
+            receiver,
+            block: vec![],
+            position: 0,
+        })
+    }
+
+    // Duration of the audio file in seconds, when known
+    pub fn duration(&self) -> Option<f32> {
+        self.source.duration()
+    }
 
-    for _ in 0..NB_AUDIO_CHANNELS {
-        println!();
+    pub fn nb_bands(&mut self) -> usize {
+        self.receiver.latest().gain.len()
     }
 
-    stream.play()?;
-    Ok((audio_data_arc, stream))
+    // Analyses the samples recorded until the time, in seconds, and returns the frames analysed
+    // since the previous call, oldest first
+    pub fn advance(&mut self, time: f32) -> Result<Vec<Data>, Box<dyn Error>> {
+        let position = (time.max(0.0) * self.source.sample_rate() as f32) as usize;
+        if position > self.position {
+            self.source
+                .read(&mut self.block, position - self.position)?;
+            self.analyzer.process(&self.block);
+            self.position = position;
+        }
+        Ok(self.receiver.new_frames())
+    }
+}
+
+// Measures the noise floor of each band during duration seconds, in dB
+pub fn calibrate(audio_config: &AudioConfig, duration: f32) -> Result<Vec<f32>, Box<dyn Error>> {
+    let (mut receiver, _handle) = init(audio_config, Box::new(meter::SilentMeter), None)?;
+
+    let mut levels: Vec<Vec<f32>> = vec![];
+    let start_time = Instant::now();
+    while start_time.elapsed().as_secs_f32() < duration {
+        thread::sleep(CALIBRATION_POLL_PERIOD);
+        for data in receiver.new_frames() {
+            levels.resize(data.level.len(), vec![]);
+            for (l, x) in levels.iter_mut().zip(&data.level) {
+                l.push(SPECTRUM_RANGE * (x - 1.0));
+            }
+        }
+    }
+
+    if levels.is_empty() {
+        return Err("No audio was analysed during the calibration".into());
+    }
+    Ok(levels
+        .into_iter()
+        .map(|mut l| {
+            l.sort_by(f32::total_cmp);
+            l[((l.len() - 1) as f32 * NOISE_FLOOR_PERCENTILE) as usize]
+        })
+        .collect())
 }
 
 fn calculate_channel_index(
@@ -163,104 +775,556 @@ fn calculate_channel_index(
     let nb_octaves = (max_freq as f32 / min_freq as f32).log2();
     let nb_octaves_per_channel = nb_octaves / nb_channels as f32;
 
-    (0..nb_channels + 1)
+    let mut index_limits: Vec<usize> = (0..nb_channels + 1)
         .map(|i| {
-            min_freq as usize * 2_f32.powf(nb_octaves_per_channel * i as f32) as usize * chunck_size
-                / sample_rate as usize
+            (min_freq as f32 * 2_f32.powf(nb_octaves_per_channel * i as f32) * chunck_size as f32
+                / sample_rate as f32) as usize
+        })
+        .collect();
+
+    // Each band contains at least one frequency bin
+    for i in 1..index_limits.len() {
+        if index_limits[i] <= index_limits[i - 1] {
+            index_limits[i] = index_limits[i - 1] + 1;
+        }
+    }
+    index_limits
+}
+
+// Validates a declared band against the sample rate and the FFT resolution
+fn calculate_band(
+    band: &BandConfig,
+    audio_config: &AudioConfig,
+    sample_rate: u32,
+    frame_rate: f32,
+) -> Result<Band, Box<dyn Error>> {
+    let (min, max) = (band.range.min, band.range.max);
+    let nyquist = sample_rate as f32 / 2.0;
+    if max > nyquist {
+        return Err(format!(
+            "Audio band {}-{} Hz is above the Nyquist frequency ({} Hz)",
+            min, max, nyquist
+        )
+        .into());
+    }
+
+    let resolution = sample_rate as f32 / audio_config.fft_size as f32;
+    let start = (min / resolution) as usize;
+    let end = (max / resolution) as usize;
+    if end <= start {
+        return Err(format!(
+            "Audio band {}-{} Hz is narrower than the FFT resolution ({:.1} Hz)",
+            min, max, resolution
+        )
+        .into());
+    }
+
+    if !(0.0..1.0).contains(&band.smoothing) {
+        return Err(format!(
+            "Invalid smoothing {} of the audio band {}-{} Hz, expected a value in [0, 1)",
+            band.smoothing, min, max
+        )
+        .into());
+    }
+
+    let stat_window = band.stat_window.unwrap_or(audio_config.stat_window);
+    let stat_window_size = calculate_stat_window(stat_window, frame_rate).ok_or_else(|| {
+        format!(
+            "Invalid statistics window of {} s of the audio band {}-{} Hz, expected a duration between an audio frame and {} s",
+            stat_window, min, max, MAX_STAT_WINDOW
+        )
+    })?;
+
+    Ok(Band {
+        start,
+        end,
+        gain: band.gain,
+        smoothing: band.smoothing,
+        stat_window_size,
+        envelope: calculate_envelope(Some(band), &audio_config.envelope, frame_rate)?,
+    })
+}
+
+// Number of frames of a statistics window of duration seconds, None when the window is shorter
+// than a frame, longer than MAX_STAT_WINDOW or not a number
+fn calculate_stat_window(duration: f32, frame_rate: f32) -> Option<usize> {
+    let size = duration * frame_rate;
+    (size >= 1.0 && duration <= MAX_STAT_WINDOW).then_some(size as usize)
+}
+
+// Envelope of a band, overriding the default one with the band config
+fn calculate_envelope(
+    band: Option<&BandConfig>,
+    envelope: &EnvelopeConfig,
+    frame_rate: f32,
+) -> Result<Envelope, Box<dyn Error>> {
+    let attack = band.and_then(|b| b.attack).unwrap_or(envelope.attack);
+    let release = band.and_then(|b| b.release).unwrap_or(envelope.release);
+    let hold = band.and_then(|b| b.hold).unwrap_or(envelope.hold);
+    if attack < 0.0 || release < 0.0 || hold < 0.0 {
+        return Err(format!(
+            "Invalid audio envelope (attack {} s, release {} s, hold {} s), expected positive durations",
+            attack, release, hold
+        )
+        .into());
+    }
+    Ok(Envelope::new(attack, release, hold, frame_rate))
+}
+
+// Range of frequency bins of each spectrum bin, on a log scale
+fn calculate_spectrum_index(
+    min_freq: u32,
+    max_freq: u32,
+    sample_rate: u32,
+    chunck_size: usize,
+) -> Vec<(usize, usize)> {
+    let nb_octaves = (max_freq as f32 / min_freq as f32).log2();
+    let max_index = chunck_size / 2 + 1;
+    let index = |i: usize| {
+        (min_freq as f32
+            * 2_f32.powf(nb_octaves * i as f32 / SPECTRUM_SIZE as f32)
+            * chunck_size as f32
+            / sample_rate as f32) as usize
+    };
+
+    (0..SPECTRUM_SIZE)
+        .map(|i| {
+            let start = index(i).min(max_index - 1);
+            let end = index(i + 1).clamp(start + 1, max_index);
+            (start, end)
         })
         .collect()
 }
 
-fn handle_input<T>(input: &[T], buffer: &mut Buffer, audio_data: &Arc<Mutex<Data>>)
+fn handle_input<T>(input: &[T], buffer: &mut Buffer, sender: &mut Sender)
 where
     T: Sample,
     f32: FromSample<T>,
 {
-    // every 2 because stereo
-    for &sample in input.iter().step_by(2) {
+    for frame in input.chunks_exact(buffer.nb_channels) {
         let pos = buffer.pos;
-        // apply window
-        buffer.input[pos] = f32::from_sample(sample) * buffer.window[pos];
+        match buffer.channel_mode {
+            ChannelMode::Select(c) => {
+                buffer.signals[0].samples[pos] = f32::from_sample(frame[c]);
+            }
+            ChannelMode::Mix => {
+                buffer.signals[0].samples[pos] = mean(frame);
+            }
+            ChannelMode::Pairs => {
+                for (signal, pair) in buffer.signals.iter_mut().zip(frame.chunks(2)) {
+                    signal.samples[pos] = mean(pair);
+                }
+            }
+        }
+        buffer.waveform[buffer.waveform_pos] = buffer.signals[0].samples[pos];
+        buffer.waveform_pos = (buffer.waveform_pos + 1) % WAVEFORM_SIZE;
+
         buffer.pos = pos + 1;
         if buffer.pos == buffer.len {
-            buffer.pos = 0;
-            buffer.count += 1;
-            buffer
-                .r2c
-                .process_with_scratch(&mut buffer.input, &mut buffer.output, &mut buffer.scratch)
-                .unwrap();
-
-            // compute levels
-            let levels: Vec<_> = (0..NB_AUDIO_CHANNELS)
-                .map(|x| {
-                    (buffer.index_limits[x] + 1..buffer.index_limits[x + 1])
-                        .fold(0.0, |acc, i| acc + buffer.output[i].norm())
-                })
-                .collect();
-
-            // update mean, sd and stat_window
-            let tmp_inv = 1.0 / (buffer.stat_window_size) as f32;
-
-            // Initialization
-            if buffer.count <= buffer.stat_window_size as u64 {
-                for (i, l) in levels.iter().enumerate().take(NB_AUDIO_CHANNELS) {
-                    buffer.stat_window[i].push_front(*l);
-                    buffer.mean[i] += tmp_inv * l;
-                    buffer.var[i] += tmp_inv * l.powi(2);
-                    if buffer.count == buffer.stat_window_size as u64 {
-                        buffer.var[i] -= buffer.mean[i].powi(2);
-                    }
-                }
+            analyse(buffer, sender);
+            // Keep the samples shared with the next frame
+            for signal in &mut buffer.signals {
+                signal.samples.copy_within(buffer.hop.., 0);
+            }
+            buffer.pos = buffer.len - buffer.hop;
+        }
+    }
+}
+
+// Clears the statistics of the bands
+fn reset(buffer: &mut Buffer) {
+    for signal in &mut buffer.signals {
+        signal.mean.fill(0.0);
+        signal.var.fill(0.0);
+        for w in &mut signal.stat_window {
+            w.clear();
+        }
+        signal.gain.fill(f32::NAN);
+    }
+}
+
+fn mean<T>(samples: &[T]) -> f32
+where
+    T: Sample,
+    f32: FromSample<T>,
+{
+    samples.iter().map(|s| f32::from_sample(*s)).sum::<f32>() / samples.len() as f32
+}
+
+fn analyse(buffer: &mut Buffer, sender: &mut Sender) {
+    buffer.count += 1;
+    let nb_bands = buffer.bands.len();
+    let data = &mut buffer.data;
+    data.gain.fill(f32::MIN);
+    let mut silence = true;
+    buffer.magnitudes.clear();
+    buffer.total_magnitudes.fill(0.0);
+
+    for (s, signal) in buffer.signals.iter_mut().enumerate() {
+        // apply window
+        for ((x, sample), w) in signal
+            .input
+            .iter_mut()
+            .zip(&signal.samples)
+            .zip(&buffer.window)
+        {
+            *x = sample * w;
+        }
+
+        buffer
+            .r2c
+            .process_with_scratch(&mut signal.input, &mut signal.output, &mut buffer.scratch)
+            .unwrap();
+
+        // compute levels
+        for (l, b) in buffer.levels.iter_mut().zip(&buffer.bands) {
+            *l = (b.start..b.end).fold(0.0, |acc, i| acc + signal.output[i].norm());
+        }
+
+        // update mean, sd and stat_window
+        for (i, (l, band)) in buffer.levels.iter().zip(&buffer.bands).enumerate() {
+            let n = signal.stat_window[i].len();
+            let tmp_inv = 1.0 / band.stat_window_size as f32;
+
+            // Initialization, over the levels received so far
+            if n < band.stat_window_size {
+                signal.stat_window[i].push_front(*l);
+                let cur_mean = signal.mean[i];
+                let n = (n + 1) as f32;
+                signal.mean[i] = cur_mean + (l - cur_mean) / n;
+                signal.var[i] += ((l - cur_mean) * (l - signal.mean[i]) - signal.var[i]) / n;
             } else {
-                for (i, l) in levels.iter().enumerate().take(NB_AUDIO_CHANNELS) {
-                    let last_val = buffer.stat_window[i].pop_back().unwrap();
-                    buffer.stat_window[i].push_front(*l);
+                let last_val = signal.stat_window[i].pop_back().unwrap();
+                signal.stat_window[i].push_front(*l);
 
-                    let cur_mean = buffer.mean[i];
+                let cur_mean = signal.mean[i];
 
-                    buffer.mean[i] = cur_mean + tmp_inv * (l - last_val);
-                    buffer.var[i] = buffer.var[i]
-                        + tmp_inv * (l.powi(2) - last_val.powi(2))
-                        + (cur_mean.powi(2) - buffer.mean[i].powi(2));
+                signal.mean[i] = cur_mean + tmp_inv * (l - last_val);
+                signal.var[i] = signal.var[i]
+                    + tmp_inv * (l.powi(2) - last_val.powi(2))
+                    + (cur_mean.powi(2) - signal.mean[i].powi(2));
 
-                    if buffer.var[i] < 0.0 {
-                        buffer.var[i] = 0.0;
-                    }
+                if signal.var[i] < 0.0 {
+                    signal.var[i] = 0.0;
                 }
             }
+        }
 
-            //check if there is at least one value above the threshold
-            let threshold = 5.0;
-            let mut above = false;
-            for x in &buffer.output {
-                if x.norm() > threshold {
-                    above = true;
-                    break;
-                }
+        // Level of the loudest bin of each band
+        for (db, b) in buffer.db_levels.iter_mut().zip(&buffer.bands) {
+            let m = signal.output[b.start..b.end]
+                .iter()
+                .fold(0.0_f32, |a, x| a.max(x.norm()));
+            *db = to_db(m, buffer.reference);
+        }
+
+        // The gains are only defined above the noise floor
+        if signal.gate.process(&buffer.db_levels) {
+            silence = false;
+            for (i, band) in buffer.bands.iter().enumerate() {
+                let sd = signal.var[i].sqrt();
+                let g = if sd > 0.0 {
+                    band.gain * (buffer.levels[i] - signal.mean[i]) / sd
+                } else {
+                    0.0
+                };
+                // Restart the smoothing from an undefined gain
+                let previous = if signal.gain[i].is_finite() {
+                    signal.gain[i]
+                } else {
+                    g
+                };
+                signal.gain[i] = band.smoothing * previous + (1.0 - band.smoothing) * g;
+                data.gain[s * nb_bands + i] = signal.gain[i];
             }
+        } else {
+            signal.gain.fill(f32::NAN);
+        }
 
-            let mut gain = [f32::MIN; NB_AUDIO_CHANNELS];
-            if above {
-                for i in 0..NB_AUDIO_CHANNELS {
-                    gain[i] = (levels[i] - buffer.mean[i]) / buffer.var[i].sqrt();
-                }
+        // Envelopes of the band levels
+        for (i, db) in buffer.db_levels.iter().enumerate() {
+            let l = normalize(*db);
+            let (e, p) = signal.envelopes[i].process(l);
+            data.level[s * nb_bands + i] = l;
+            data.envelope[s * nb_bands + i] = e;
+            data.peak[s * nb_bands + i] = p;
+        }
+
+        buffer.magnitudes.extend(
+            signal.output[buffer.magnitude_range.0..buffer.magnitude_range.1]
+                .iter()
+                .map(|x| x.norm()),
+        );
+        for (t, x) in buffer.total_magnitudes.iter_mut().zip(&signal.output) {
+            *t += x.norm();
+        }
+    }
+
+    for (x, (start, end)) in data.spectrum.iter_mut().zip(&buffer.spectrum_limits) {
+        let m = buffer.total_magnitudes[*start..*end]
+            .iter()
+            .fold(0.0_f32, |a, b| a.max(*b));
+        *x = normalize(to_db(m, buffer.reference));
+    }
+
+    // Oldest samples first
+    let (newest, oldest) = buffer.waveform.split_at(buffer.waveform_pos);
+    data.waveform[..oldest.len()].copy_from_slice(oldest);
+    data.waveform[oldest.len()..].copy_from_slice(newest);
+
+    // Onset and beat tracking over the analysed frequency range
+    let (flux, onset) = buffer.onset_detector.process(&buffer.magnitudes);
+    let clock = buffer.clock.as_mut().and_then(ClockReceiver::now);
+    buffer.beat_tracker.process(flux, onset, clock);
+
+    data.frame = buffer.count as u32;
+    data.onset = onset;
+    data.silence = silence;
+    data.bpm = buffer.beat_tracker.bpm;
+    data.beat_phase = buffer.beat_tracker.phase;
+    data.beat_count = buffer.beat_tracker.count;
+    sender.send(data);
+}
+
+fn to_db(magnitude: f32, reference: f32) -> f32 {
+    20.0 * (magnitude / reference).log10()
+}
+
+// Level between 0 (-SPECTRUM_RANGE dB) and 1 (0 dB)
+fn normalize(db: f32) -> f32 {
+    ((db + SPECTRUM_RANGE) / SPECTRUM_RANGE).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use meter::ProbeMeter;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::path::Path;
+
+    // Counts the allocations and deallocations of the current thread
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    pub fn test_config(nb_bands: usize) -> AudioConfig {
+        AudioConfig {
+            host: None,
+            device: None,
+            file: None,
+            speed: 0.0,
+            playback: false,
+            channel_mode: ChannelMode::Select(0),
+            nb_bands,
+            bands: vec![],
+            envelope: EnvelopeConfig::default(),
+            stat_window: 2.0,
+            noise_floors: None,
+            fft_size: 1024,
+            hop_size: 512,
+            window: WindowFunction::Hann,
+            min_freq: 20,
+            max_freq: 20000,
+            meter: meter::MeterKind::None,
+        }
+    }
+
+    // Kicks on a low tone at 120 BPM, with a high tone in between
+    pub fn test_signal(duration: f32) -> Vec<f32> {
+        (0..(duration * SAMPLE_RATE as f32) as usize)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let beat = (t * 2.0).fract();
+                let kick = (-beat * 20.0).exp() * (2.0 * PI * 60.0 * t).sin();
+                0.6 * kick + 0.1 * (2.0 * PI * 3000.0 * t).sin()
+            })
+            .collect()
+    }
+
+    pub fn write_wav(path: &Path, samples: &[f32]) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for s in samples {
+            writer.write_sample((s * i16::MAX as f32) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn gains_follow_the_kicks() {
+        // One second of silence before the kicks
+        let mut signal = vec![0.0; SAMPLE_RATE as usize];
+        signal.extend(test_signal(6.0));
+        let path = std::env::temp_dir().join(format!("gseq_kicks_{}.wav", std::process::id()));
+        write_wav(&path, &signal);
+
+        let config = test_config(3);
+        let mut source = FileSource::new(&path, 0.0, false).unwrap();
+        let probe = ProbeMeter::new();
+        let (mut analyzer, mut receiver) =
+            analyzer(&config, SAMPLE_RATE, 1, Box::new(probe.clone()), None).unwrap();
+        let mut block = vec![];
+        let mut nb_frames = 0;
+        loop {
+            source.read(&mut block, SAMPLE_RATE as usize / 4).unwrap();
+            if block.is_empty() {
+                break;
             }
+            analyzer.process(&block);
+            nb_frames += receiver.new_frames().len();
+            // The meter catches up before its ring fills up
+            let start = Instant::now();
+            while probe.frames().len() < nb_frames && start.elapsed() < Duration::from_secs(5) {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
 
-            let mut stdout = stdout();
-            stdout.execute(cursor::MoveUp(gain.len() as u16)).unwrap();
-            stdout
-                .execute(terminal::Clear(terminal::ClearType::FromCursorDown))
-                .unwrap();
-            for (i, g) in gain.iter().enumerate() {
-                if *g == f32::MIN {
-                    writeln!(stdout, "audio_channel[{}]: -INF", i).unwrap();
-                } else {
-                    writeln!(stdout, "audio_channel[{}]: {}", i, g).unwrap();
+        let frames = probe.frames();
+        assert_eq!(frames.len(), nb_frames);
+        let time = |d: &Data| (d.frame as usize * config.hop_size) as f32 / SAMPLE_RATE as f32;
+
+        for d in frames.iter().filter(|d| time(d) < 0.9) {
+            assert!(d.silence);
+            assert!(d.gain.iter().all(|g| *g == f32::MIN));
+        }
+
+        // The kicks stand out of the statistics window of the low band
+        let (mut on_beat, mut off_beat) = (vec![], vec![]);
+        for d in frames.iter().filter(|d| time(d) > 4.0) {
+            assert!(!d.silence);
+            assert_eq!(d.gain.len(), 3);
+            assert!(d.gain.iter().all(|g| g.is_finite()));
+            let phase = ((time(d) - 1.0) * 2.0).fract();
+            if phase < 0.1 {
+                on_beat.push(d.gain[0]);
+            } else if phase > 0.5 {
+                off_beat.push(d.gain[0]);
+            }
+        }
+        let mean = |g: &[f32]| g.iter().sum::<f32>() / g.len() as f32;
+        assert!(mean(&on_beat) > 1.0);
+        assert!(mean(&off_beat) < 0.0);
+    }
+
+    #[test]
+    fn sending_does_not_allocate() {
+        let (latest, _latest_output) = triple_buffer::triple_buffer(&Data::new(2));
+        let (history, mut history_consumer, mut history_pool) = FrameRing::new(4, 2);
+        let (meter, _meter_consumer, _meter_pool) = FrameRing::new(4, 2);
+        let mut sender = Sender {
+            latest,
+            history,
+            meter,
+        };
+        let mut data = Data::new(2);
+        let mut received = Vec::with_capacity(10);
+
+        ALLOCATIONS.with(|a| a.set(0));
+        for i in 0..10 {
+            data.frame = i;
+            data.gain.fill(i as f32);
+            // The frames are dropped once the meter ring is full
+            sender.send(&data);
+            if i % 3 == 2 {
+                while let Ok(d) = history_consumer.pop() {
+                    received.push(d.frame);
+                    let _ = history_pool.push(d);
                 }
             }
-            let mut audio_data = audio_data.lock().unwrap();
-            audio_data.gain = gain;
-            return;
+        }
+        assert_eq!(ALLOCATIONS.with(|a| a.get()), 0);
+
+        while let Ok(d) = history_consumer.pop() {
+            assert_eq!(d.gain, [d.frame as f32; 2]);
+            received.push(d.frame);
+        }
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn analysis_does_not_allocate() {
+        let (mut analyzer, mut receiver) = analyzer(
+            &test_config(3),
+            SAMPLE_RATE,
+            1,
+            Box::new(meter::SilentMeter),
+            None,
+        )
+        .unwrap();
+        let signal = test_signal(20.0);
+        let (warmup, measured) = signal.split_at(signal.len() / 2);
+
+        // The statistics windows and the tempo history are filled first
+        for block in warmup.chunks(512) {
+            analyzer.process(block);
+            receiver.new_frames();
+        }
+
+        let mut allocations = 0;
+        for block in measured.chunks(512) {
+            let before = ALLOCATIONS.with(Cell::get);
+            analyzer.process(block);
+            allocations += ALLOCATIONS.with(Cell::get) - before;
+            assert!(!receiver.new_frames().is_empty() || block.len() < 512);
+        }
+        assert_eq!(allocations, 0);
+    }
+
+    #[test]
+    fn invalid_stat_window() {
+        let create = |config: &AudioConfig| {
+            analyzer(config, SAMPLE_RATE, 1, Box::new(meter::SilentMeter), None).is_ok()
+        };
+        assert!(create(&test_config(3)));
+        let band = BandConfig {
+            range: crate::config::FreqRange {
+                min: 40.0,
+                max: 120.0,
+            },
+            gain: 1.0,
+            smoothing: 0.0,
+            stat_window: None,
+            attack: None,
+            release: None,
+            hold: None,
+        };
+        for duration in [f32::NAN, f32::INFINITY, 0.0, -1.0, 0.001, 1e30] {
+            let mut config = test_config(3);
+            config.stat_window = duration;
+            assert!(!create(&config), "{duration}");
+
+            let mut config = test_config(1);
+            config.bands = vec![BandConfig {
+                stat_window: Some(duration),
+                ..band.clone()
+            }];
+            assert!(!create(&config), "{duration}");
         }
     }
 }
@@ -0,0 +1,288 @@
+use super::clock::ClockTime;
+use std::collections::VecDeque;
+
+// Duration of the spectral flux history used by the adaptive threshold, in seconds
+const ONSET_WINDOW_DURATION: f32 = 1.0;
+// The flux must exceed mean + ONSET_SENSITIVITY * sd to be an onset
+const ONSET_SENSITIVITY: f32 = 1.5;
+// Minimum time between two onsets, in seconds
+const ONSET_MIN_INTERVAL: f32 = 0.1;
+
+// Duration of the onset strength history used to estimate the tempo, in seconds
+const TEMPO_WINDOW_DURATION: f32 = 6.0;
+// Time between two tempo estimations, in seconds
+const TEMPO_UPDATE_PERIOD: f32 = 1.0;
+const MIN_BPM: f32 = 60.0;
+const MAX_BPM: f32 = 180.0;
+const DEFAULT_BPM: f32 = 120.0;
+// Fraction of the phase error corrected at each onset
+const PHASE_CORRECTION: f32 = 0.2;
+
+pub struct OnsetDetector {
+    previous: Vec<f32>,
+    flux_window: VecDeque<f32>,
+    flux_window_size: usize,
+    previous_flux: f32,
+    min_interval: usize,
+    frames_since_onset: usize,
+}
+
+impl OnsetDetector {
+    // frame_rate is the number of analysed chunks per second
+    pub fn new(nb_bins: usize, frame_rate: f32) -> Self {
+        let min_interval = (ONSET_MIN_INTERVAL * frame_rate).ceil() as usize;
+        let flux_window_size = (ONSET_WINDOW_DURATION * frame_rate).ceil() as usize;
+        OnsetDetector {
+            previous: vec![0.0; nb_bins],
+            flux_window: VecDeque::with_capacity(flux_window_size + 1),
+            flux_window_size,
+            previous_flux: 0.0,
+            min_interval,
+            frames_since_onset: min_interval,
+        }
+    }
+
+    // Returns the spectral flux of the chunk and whether it is an onset
+    pub fn process(&mut self, magnitudes: &[f32]) -> (f32, bool) {
+        // Half-wave rectified difference of the log magnitudes
+        let mut flux = 0.0;
+        for (p, x) in self.previous.iter_mut().zip(magnitudes) {
+            let m = x.ln_1p();
+            flux += (m - *p).max(0.0);
+            *p = m;
+        }
+
+        let n = self.flux_window.len() as f32;
+        let onset = if n > 0.0 {
+            let mean = self.flux_window.iter().sum::<f32>() / n;
+            let var = self
+                .flux_window
+                .iter()
+                .map(|f| (f - mean).powi(2))
+                .sum::<f32>()
+                / n;
+            let threshold = mean + ONSET_SENSITIVITY * var.sqrt();
+            flux > threshold
+                && flux > self.previous_flux
+                && self.frames_since_onset >= self.min_interval
+        } else {
+            false
+        };
+
+        self.flux_window.push_front(flux);
+        if self.flux_window.len() > self.flux_window_size {
+            self.flux_window.pop_back();
+        }
+        self.previous_flux = flux;
+
+        if onset {
+            self.frames_since_onset = 0;
+        } else {
+            self.frames_since_onset += 1;
+        }
+
+        (flux, onset)
+    }
+}
+
+pub struct BeatTracker {
+    frame_rate: f32,
+    envelope: VecDeque<f32>,
+    envelope_size: usize,
+    // Scratch buffers of the tempo estimation, allocated once
+    centered: Vec<f32>,
+    acf: Vec<f32>,
+    update_period: usize,
+    frames_since_update: usize,
+    pub bpm: f32,
+    pub phase: f32,
+    pub count: u32,
+}
+
+impl BeatTracker {
+    pub fn new(frame_rate: f32) -> Self {
+        let envelope_size = (TEMPO_WINDOW_DURATION * frame_rate).ceil() as usize;
+        BeatTracker {
+            frame_rate,
+            envelope: VecDeque::with_capacity(envelope_size + 1),
+            envelope_size,
+            centered: Vec::with_capacity(envelope_size),
+            acf: Vec::with_capacity(envelope_size + 4),
+            update_period: (TEMPO_UPDATE_PERIOD * frame_rate).ceil() as usize,
+            frames_since_update: 0,
+            bpm: DEFAULT_BPM,
+            phase: 0.0,
+            count: 0,
+        }
+    }
+
+    // The tempo, and the beats when they are known, of the external clock replace the tracking
+    pub fn process(&mut self, flux: f32, onset: bool, clock: Option<ClockTime>) {
+        self.envelope.push_back(flux);
+        if self.envelope.len() > self.envelope_size {
+            self.envelope.pop_front();
+        }
+
+        if let Some(c) = clock {
+            self.bpm = c.bpm;
+            if let Some(beat) = c.beat {
+                self.phase = beat.rem_euclid(1.0) as f32;
+                self.count = beat.floor() as u32;
+                return;
+            }
+        }
+
+        self.frames_since_update += 1;
+        if clock.is_none()
+            && self.frames_since_update >= self.update_period
+            && self.envelope.len() == self.envelope_size
+        {
+            self.frames_since_update = 0;
+            if let Some(bpm) = self.estimate_bpm() {
+                self.bpm = bpm;
+            }
+        }
+
+        // Advance the phase, one unit per beat
+        self.phase += self.bpm / (60.0 * self.frame_rate);
+        while self.phase >= 1.0 {
+            self.phase -= 1.0;
+            self.count = self.count.wrapping_add(1);
+        }
+
+        // Pull the phase towards the closest beat
+        if onset {
+            let error = if self.phase > 0.5 {
+                self.phase - 1.0
+            } else {
+                self.phase
+            };
+            self.phase -= PHASE_CORRECTION * error;
+            if self.phase >= 1.0 {
+                self.phase -= 1.0;
+                self.count = self.count.wrapping_add(1);
+            }
+        }
+    }
+
+    // Autocorrelation of the onset strength over the tempo range
+    fn estimate_bpm(&mut self) -> Option<f32> {
+        let n = self.envelope.len();
+        let mean = self.envelope.iter().sum::<f32>() / n as f32;
+        self.centered.clear();
+        self.centered.extend(self.envelope.iter().map(|x| x - mean));
+        let e = &self.centered;
+
+        let min_lag = ((60.0 * self.frame_rate / MAX_BPM).floor() as usize).max(2);
+        let max_lag = ((60.0 * self.frame_rate / MIN_BPM).ceil() as usize).min(n - 2);
+        if min_lag + 1 >= max_lag {
+            return None;
+        }
+
+        self.acf.clear();
+        self.acf.extend(
+            (min_lag - 2..max_lag + 3).map(|lag| (lag..n).map(|i| e[i] * e[i - lag]).sum::<f32>()),
+        );
+        // Sum the neighbouring lags, the onsets of a period between two chunks are split between
+        // them and a multiple of the period would win otherwise
+        for i in 0..self.acf.len() - 2 {
+            self.acf[i] += self.acf[i + 1] + self.acf[i + 2];
+        }
+        self.acf.truncate(self.acf.len() - 2);
+        let acf = &self.acf;
+
+        let (best, max) = (1..acf.len() - 1)
+            .map(|i| (i, acf[i]))
+            .fold((0, f32::MIN), |a, b| if b.1 > a.1 { b } else { a });
+        if max <= 0.0 {
+            return None;
+        }
+
+        // Parabolic interpolation of the peak
+        let (l, c, r) = (acf[best - 1], acf[best], acf[best + 1]);
+        let denom = l - 2.0 * c + r;
+        let offset = if denom != 0.0 {
+            (0.5 * (l - r) / denom).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        let lag = (min_lag + best - 1) as f32 + offset;
+
+        Some(60.0 * self.frame_rate / lag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_RATE: f32 = 44100.0 / 512.0;
+    const NB_BINS: usize = 64;
+
+    // Magnitudes of the chunks of a click track, with low noise between the clicks, and whether
+    // each chunk is a click
+    fn click_track(bpm: f32, duration: f32) -> Vec<(Vec<f32>, bool)> {
+        let period = 60.0 * FRAME_RATE / bpm;
+        let mut next_beat = 1;
+        let mut seed = 1_u32;
+        (0..(duration * FRAME_RATE) as usize)
+            .map(|i| {
+                let click = i == (next_beat as f32 * period).round() as usize;
+                if click {
+                    next_beat += 1;
+                }
+                let magnitudes = (0..NB_BINS)
+                    .map(|_| {
+                        seed ^= seed << 13;
+                        seed ^= seed >> 17;
+                        seed ^= seed << 5;
+                        let noise = 0.05 * (seed as f32 / u32::MAX as f32);
+                        if click {
+                            10.0 + noise
+                        } else {
+                            noise
+                        }
+                    })
+                    .collect();
+                (magnitudes, click)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn onsets_on_clicks() {
+        let mut detector = OnsetDetector::new(NB_BINS, FRAME_RATE);
+        let track = click_track(128.0, 10.0);
+        for (magnitudes, click) in &track {
+            let (_, onset) = detector.process(magnitudes);
+            assert_eq!(onset, *click);
+        }
+        assert_eq!(track.iter().filter(|(_, c)| *c).count(), 21);
+    }
+
+    #[test]
+    fn tracked_tempo() {
+        for bpm in [75.0, 128.0, 170.0] {
+            let mut detector = OnsetDetector::new(NB_BINS, FRAME_RATE);
+            let mut tracker = BeatTracker::new(FRAME_RATE);
+            let track = click_track(bpm, 20.0);
+            let mut nb_onsets = 0;
+            let mut phase_errors = vec![];
+            for (i, (magnitudes, click)) in track.iter().enumerate() {
+                let (flux, onset) = detector.process(magnitudes);
+                tracker.process(flux, onset, None);
+                nb_onsets += onset as usize;
+                // The beats are aligned on the clicks once the tempo is known
+                if *click && i as f32 > 15.0 * FRAME_RATE {
+                    phase_errors.push(tracker.phase.min(1.0 - tracker.phase));
+                }
+            }
+            assert_eq!(nb_onsets, track.iter().filter(|(_, c)| *c).count(), "{bpm}");
+            assert!((tracker.bpm - bpm).abs() < 1.0, "{bpm}: {}", tracker.bpm);
+            assert!(
+                phase_errors.iter().all(|e| *e < 0.1),
+                "{bpm}: {phase_errors:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+use triple_buffer::{triple_buffer, Input, Output};
+
+// The external clock is ignored when it has not been updated for CLOCK_TIMEOUT
+const CLOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Source of the tempo and of the beats
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClockSource {
+    // Beat tracking of the audio input
+    #[default]
+    Audio,
+    // MIDI clock (24 pulses per quarter note) of the MIDI input port
+    Midi,
+    // Tempo of the Ableton Link session on the local network
+    Link,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ClockState {
+    pub bpm: f32,
+    // Beat position at the update time, None when only the tempo is known
+    pub beat: Option<f64>,
+    // Number of beats during which the position is extrapolated
+    pub horizon: f64,
+    pub updated: Instant,
+}
+
+// Tempo and beat position of the external clock
+#[derive(Clone, Copy, Debug)]
+pub struct ClockTime {
+    pub bpm: f32,
+    pub beat: Option<f64>,
+}
+
+pub struct ClockSender(Input<Option<ClockState>>);
+
+impl ClockSender {
+    // None when the clock is stopped
+    pub fn send(&mut self, state: Option<ClockState>) {
+        self.0.write(state);
+    }
+}
+
+pub struct ClockReceiver(Output<Option<ClockState>>);
+
+impl ClockReceiver {
+    // None when the clock is stopped or has timed out
+    pub fn now(&mut self) -> Option<ClockTime> {
+        let state = (*self.0.read())?;
+        let elapsed = state.updated.elapsed();
+        if elapsed > CLOCK_TIMEOUT {
+            return None;
+        }
+        let beats = (elapsed.as_secs_f64() * state.bpm as f64 / 60.0).min(state.horizon);
+        Some(ClockTime {
+            bpm: state.bpm,
+            beat: state.beat.map(|b| b + beats),
+        })
+    }
+}
+
+pub fn channel() -> (ClockSender, ClockReceiver) {
+    let (input, output) = triple_buffer(&None);
+    (ClockSender(input), ClockReceiver(output))
+}
@@ -0,0 +1,102 @@
+// Attack/release envelope follower with a peak hold
+#[derive(Clone, Debug)]
+pub struct Envelope {
+    attack: f32,
+    release: f32,
+    // Number of frames during which the peak is held
+    hold: usize,
+    value: f32,
+    peak: f32,
+    hold_count: usize,
+}
+
+// Coefficient of a one-pole filter reaching 63% of a step in duration seconds
+fn coefficient(duration: f32, frame_rate: f32) -> f32 {
+    if duration > 0.0 {
+        (-1.0 / (duration * frame_rate)).exp()
+    } else {
+        0.0
+    }
+}
+
+impl Envelope {
+    // Durations in seconds, frame_rate is the number of analysed frames per second
+    pub fn new(attack: f32, release: f32, hold: f32, frame_rate: f32) -> Self {
+        Envelope {
+            attack: coefficient(attack, frame_rate),
+            release: coefficient(release, frame_rate),
+            hold: (hold * frame_rate) as usize,
+            value: 0.0,
+            peak: 0.0,
+            hold_count: 0,
+        }
+    }
+
+    // Returns the envelope and its peak
+    pub fn process(&mut self, level: f32) -> (f32, f32) {
+        let coef = if level > self.value {
+            self.attack
+        } else {
+            self.release
+        };
+        self.value = coef * self.value + (1.0 - coef) * level;
+
+        if self.value >= self.peak {
+            self.peak = self.value;
+            self.hold_count = self.hold;
+        } else if self.hold_count > 0 {
+            self.hold_count -= 1;
+        } else {
+            self.peak = (self.release * self.peak).max(self.value);
+        }
+
+        (self.value, self.peak)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_RATE: f32 = 100.0;
+
+    #[test]
+    fn attack_and_release() {
+        let mut envelope = Envelope::new(0.1, 0.5, 0.0, FRAME_RATE);
+        let mut value = 0.0;
+        for _ in 0..10 {
+            value = envelope.process(1.0).0;
+        }
+        // 63% of the step after the attack duration
+        assert!((value - (1.0 - (-1.0_f32).exp())).abs() < 1e-3);
+
+        for _ in 0..1000 {
+            envelope.process(1.0);
+        }
+        let top = envelope.process(1.0).0;
+        for _ in 0..50 {
+            value = envelope.process(0.0).0;
+        }
+        assert!((value - top * (-1.0_f32).exp()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn instantaneous() {
+        let mut envelope = Envelope::new(0.0, 0.0, 0.0, FRAME_RATE);
+        assert_eq!(envelope.process(0.8), (0.8, 0.8));
+        assert_eq!(envelope.process(0.2), (0.2, 0.2));
+    }
+
+    #[test]
+    fn peak_hold() {
+        let mut envelope = Envelope::new(0.0, 0.0, 0.05, FRAME_RATE);
+        envelope.process(1.0);
+        // The peak is held during 5 frames
+        for _ in 0..5 {
+            assert_eq!(envelope.process(0.5), (0.5, 1.0));
+        }
+        // then released down to the envelope
+        assert_eq!(envelope.process(0.5), (0.5, 0.5));
+        assert_eq!(envelope.process(0.7), (0.7, 0.7));
+    }
+}
@@ -0,0 +1,335 @@
+use super::{Analyzer, AudioSource, SourceHandle};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rtrb::{Producer, RingBuffer};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::mem;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Number of frames fed to the analyzer at once
+const BLOCK_SIZE: usize = 512;
+// Number of decoded frames waiting for the output device
+const PLAYBACK_CAPACITY: usize = 16 * BLOCK_SIZE;
+// Time between two checks of the free space of the playback buffer
+const PLAYBACK_POLL_PERIOD: Duration = Duration::from_millis(5);
+
+// Reader of the samples of a file, decoding them as they are needed
+enum Decoder {
+    Wav(hound::WavReader<BufReader<File>>),
+    Flac {
+        reader: claxon::FlacReader<File>,
+        // Last decoded FLAC frame and position of the next sample, channels interleaved
+        block: claxon::Block,
+        position: u32,
+    },
+}
+
+pub struct FileSource {
+    decoder: Decoder,
+    // Scale of the integer samples
+    scale: f32,
+    channels: u16,
+    sample_rate: u32,
+    // Number of frames of the file, when known
+    nb_frames: Option<u64>,
+    speed: f32,
+    playback: bool,
+}
+
+impl Decoder {
+    // Appends at most len samples to the buffer, fewer at the end of the file
+    fn read(
+        &mut self,
+        buffer: &mut Vec<f32>,
+        len: usize,
+        scale: f32,
+    ) -> Result<(), Box<dyn Error>> {
+        match self {
+            Decoder::Wav(reader) => match reader.spec().sample_format {
+                hound::SampleFormat::Float => {
+                    for s in reader.samples::<f32>().take(len) {
+                        buffer.push(s?);
+                    }
+                }
+                hound::SampleFormat::Int => {
+                    for s in reader.samples::<i32>().take(len) {
+                        buffer.push(s? as f32 * scale);
+                    }
+                }
+            },
+            Decoder::Flac {
+                reader,
+                block,
+                position,
+            } => {
+                let end = buffer.len() + len;
+                while buffer.len() < end {
+                    if *position == block.len() {
+                        // The buffer of the previous FLAC frame is reused
+                        let previous = mem::replace(block, claxon::Block::empty()).into_buffer();
+                        match reader.blocks().read_next_or_eof(previous)? {
+                            Some(b) => *block = b,
+                            None => break,
+                        }
+                        *position = 0;
+                    }
+                    let channels = block.channels();
+                    let s = block.sample(*position % channels, *position / channels);
+                    buffer.push(s as f32 * scale);
+                    *position += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FileSource {
+    pub fn new(path: &Path, speed: f32, playback: bool) -> Result<Self, Box<dyn Error>> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+
+        let (decoder, bits_per_sample, channels, sample_rate, nb_frames) =
+            match extension.as_deref() {
+                Some("wav") => {
+                    let reader = hound::WavReader::open(path)?;
+                    let spec = reader.spec();
+                    let nb_frames = reader.duration() as u64;
+                    (
+                        Decoder::Wav(reader),
+                        spec.bits_per_sample as u32,
+                        spec.channels,
+                        spec.sample_rate,
+                        Some(nb_frames),
+                    )
+                }
+                Some("flac") => {
+                    let reader = claxon::FlacReader::open(path)?;
+                    let info = reader.streaminfo();
+                    (
+                        Decoder::Flac {
+                            reader,
+                            block: claxon::Block::empty(),
+                            position: 0,
+                        },
+                        info.bits_per_sample,
+                        info.channels as u16,
+                        info.sample_rate,
+                        info.samples,
+                    )
+                }
+                _ => {
+                    return Err(format!(
+                        "Unsupported audio file {} (expected WAV or FLAC)",
+                        path.display()
+                    )
+                    .into())
+                }
+            };
+
+        // The integer samples are decoded as i32
+        if !(1..=32).contains(&bits_per_sample) {
+            return Err(format!(
+                "Unsupported audio file {} ({} bits per sample)",
+                path.display(),
+                bits_per_sample
+            )
+            .into());
+        }
+
+        println!(
+            "[AUDIO FILE] {} ({} Hz, {} channels)",
+            path.display(),
+            sample_rate,
+            channels
+        );
+
+        if playback && speed != 1.0 {
+            log::warn!("The audio file is played back in real time, ignoring the speed");
+        }
+
+        Ok(FileSource {
+            decoder,
+            scale: 1.0 / (1_i64 << (bits_per_sample - 1)) as f32,
+            channels,
+            sample_rate,
+            nb_frames,
+            speed,
+            playback,
+        })
+    }
+
+    // Duration of the file in seconds, when known
+    pub fn duration(&self) -> Option<f32> {
+        self.nb_frames.map(|n| n as f32 / self.sample_rate as f32)
+    }
+
+    // Replaces the content of the buffer by the next interleaved samples of at most nb_frames
+    // frames, fewer at the end of the file
+    pub fn read(&mut self, buffer: &mut Vec<f32>, nb_frames: usize) -> Result<(), Box<dyn Error>> {
+        buffer.clear();
+        self.decoder
+            .read(buffer, nb_frames * self.channels as usize, self.scale)
+    }
+
+    fn start_thread(mut self, mut analyzer: Analyzer) -> Result<SourceHandle, Box<dyn Error>> {
+        let thread = thread::Builder::new()
+            .name("audio_file".into())
+            .spawn(move || {
+                let mut block = Vec::with_capacity(BLOCK_SIZE * self.channels as usize);
+                let start_time = Instant::now();
+                for i in 0.. {
+                    if let Err(e) = self.read(&mut block, BLOCK_SIZE) {
+                        log::error!("Failed to decode the audio file: {e}");
+                        break;
+                    }
+                    if block.is_empty() {
+                        break;
+                    }
+                    // Wait until the block would have been recorded
+                    if self.speed > 0.0 {
+                        let end =
+                            ((i + 1) * BLOCK_SIZE) as f32 / (self.sample_rate as f32 * self.speed);
+                        let elapsed = start_time.elapsed().as_secs_f32();
+                        if end > elapsed {
+                            thread::sleep(Duration::from_secs_f32(end - elapsed));
+                        }
+                    }
+                    analyzer.process(&block);
+                }
+                log::info!("End of the audio file");
+            })?;
+
+        Ok(SourceHandle::from_thread(thread))
+    }
+
+    // Decodes the file ahead of the output device, until the end of the file or of the stream
+    fn feed_playback(mut self, mut producer: Producer<f32>) {
+        let mut block = Vec::with_capacity(BLOCK_SIZE * self.channels as usize);
+        loop {
+            if let Err(e) = self.read(&mut block, BLOCK_SIZE) {
+                log::error!("Failed to decode the audio file: {e}");
+                return;
+            }
+            if block.is_empty() {
+                return;
+            }
+            while producer.slots() < block.len() {
+                if producer.is_abandoned() {
+                    return;
+                }
+                thread::sleep(PLAYBACK_POLL_PERIOD);
+            }
+            if let Ok(mut chunk) = producer.write_chunk(block.len()) {
+                let (first, second) = chunk.as_mut_slices();
+                let (a, b) = block.split_at(first.len());
+                first.copy_from_slice(a);
+                second.copy_from_slice(b);
+                chunk.commit_all();
+            }
+        }
+    }
+
+    fn start_playback(self, mut analyzer: Analyzer) -> Result<SourceHandle, Box<dyn Error>> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("No audio output device available")?;
+
+        println!("[AUDIO OUTPUT DEVICE] {}", device.name()?);
+
+        let config = cpal::StreamConfig {
+            channels: self.channels,
+            sample_rate: cpal::SampleRate(self.sample_rate),
+            buffer_size: cpal::BufferSize::Default,
+        };
+
+        let err_fn = move |err| {
+            eprintln!("an error occurred on stream: {}", err);
+        };
+
+        let (producer, mut consumer) = RingBuffer::new(PLAYBACK_CAPACITY * self.channels as usize);
+        let stream = device.build_output_stream(
+            &config,
+            move |data: &mut [f32], _: &_| {
+                // Silence until the reader thread catches up or after the end of the file
+                let n = consumer.slots().min(data.len());
+                if let Ok(chunk) = consumer.read_chunk(n) {
+                    let (first, second) = chunk.as_slices();
+                    data[..first.len()].copy_from_slice(first);
+                    data[first.len()..n].copy_from_slice(second);
+                    chunk.commit_all();
+                }
+                data[n..].fill(0.0);
+                analyzer.process(&data[..n]);
+            },
+            err_fn,
+            None,
+        )?;
+
+        // The reader thread stops with the stream
+        let thread = thread::Builder::new()
+            .name("audio_file".into())
+            .spawn(move || self.feed_playback(producer))?;
+
+        stream.play()?;
+        Ok(SourceHandle {
+            _stream: Some(stream),
+            _thread: Some(thread),
+        })
+    }
+}
+
+impl AudioSource for FileSource {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn nb_channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn start(self: Box<Self>, analyzer: Analyzer) -> Result<SourceHandle, Box<dyn Error>> {
+        if self.playback {
+            self.start_playback(analyzer)
+        } else {
+            self.start_thread(analyzer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::tests::write_wav;
+
+    #[test]
+    fn read_in_blocks() {
+        let samples: Vec<f32> = (0..1000).map(|i| (i as f32 / 1000.0) - 0.5).collect();
+        let path = std::env::temp_dir().join(format!("gseq_ramp_{}.wav", std::process::id()));
+        write_wav(&path, &samples);
+
+        let mut source = FileSource::new(&path, 0.0, false).unwrap();
+        assert_eq!(source.duration(), Some(1000.0 / 44100.0));
+        let mut block = vec![];
+        let mut decoded = vec![];
+        loop {
+            source.read(&mut block, 300).unwrap();
+            if block.is_empty() {
+                break;
+            }
+            assert!(block.len() <= 300);
+            decoded.extend_from_slice(&block);
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(decoded.len(), samples.len());
+        for (d, s) in decoded.iter().zip(&samples) {
+            assert!((d - s).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+// The gate opens when a band is GATE_OPEN_MARGIN dB above its noise floor
+const GATE_OPEN_MARGIN: f32 = 6.0;
+// and closes when all the bands are less than GATE_CLOSE_MARGIN dB above their noise floor
+const GATE_CLOSE_MARGIN: f32 = 3.0;
+// for at least GATE_HOLD_DURATION seconds
+const GATE_HOLD_DURATION: f32 = 0.5;
+
+// Noise gate with hysteresis over the band levels
+pub struct Gate {
+    open_thresholds: Vec<f32>,
+    close_thresholds: Vec<f32>,
+    hold: usize,
+    count: usize,
+    open: bool,
+}
+
+impl Gate {
+    // floors are the noise floors of the bands, in dB
+    pub fn new(floors: &[f32], frame_rate: f32) -> Self {
+        Gate {
+            open_thresholds: floors.iter().map(|f| f + GATE_OPEN_MARGIN).collect(),
+            close_thresholds: floors.iter().map(|f| f + GATE_CLOSE_MARGIN).collect(),
+            hold: (GATE_HOLD_DURATION * frame_rate).ceil() as usize,
+            count: 0,
+            open: false,
+        }
+    }
+
+    // Returns whether the gate is open, levels in dB
+    pub fn process(&mut self, levels: &[f32]) -> bool {
+        if levels.iter().zip(&self.open_thresholds).any(|(l, t)| l > t) {
+            self.open = true;
+            self.count = 0;
+        } else if levels
+            .iter()
+            .zip(&self.close_thresholds)
+            .all(|(l, t)| l < t)
+        {
+            self.count += 1;
+            if self.count >= self.hold {
+                self.open = false;
+            }
+        } else {
+            self.count = 0;
+        }
+        self.open
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Frame rate holding the gate during 5 frames
+    const FRAME_RATE: f32 = 10.0;
+
+    #[test]
+    fn opens_above_margin() {
+        let mut gate = Gate::new(&[-50.0, -40.0], FRAME_RATE);
+        assert!(!gate.process(&[-45.0, -35.0]));
+        // One band above its open threshold is enough
+        assert!(gate.process(&[-60.0, -33.0]));
+    }
+
+    #[test]
+    fn hysteresis() {
+        let mut gate = Gate::new(&[-50.0], FRAME_RATE);
+        assert!(gate.process(&[-40.0]));
+        // Between the close and open thresholds, the gate stays open
+        for _ in 0..20 {
+            assert!(gate.process(&[-46.0]));
+        }
+        // Below the close threshold, it closes after the hold
+        for _ in 0..4 {
+            assert!(gate.process(&[-48.0]));
+        }
+        assert!(!gate.process(&[-48.0]));
+        // and stays closed between the thresholds
+        assert!(!gate.process(&[-46.0]));
+    }
+
+    #[test]
+    fn hold_restarts() {
+        let mut gate = Gate::new(&[-50.0], FRAME_RATE);
+        gate.process(&[-40.0]);
+        for _ in 0..4 {
+            assert!(gate.process(&[-48.0]));
+        }
+        // A level above the close threshold restarts the hold
+        assert!(gate.process(&[-46.0]));
+        for _ in 0..4 {
+            assert!(gate.process(&[-48.0]));
+        }
+        assert!(!gate.process(&[-48.0]));
+    }
+}
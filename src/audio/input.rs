@@ -0,0 +1,144 @@
+use super::{Analyzer, AudioConfig, AudioSource, SourceHandle};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use promptly::prompt_default;
+use std::error::Error;
+
+pub struct InputSource {
+    device: cpal::Device,
+    config: cpal::SupportedStreamConfig,
+}
+
+fn select_host(name: Option<&str>) -> Result<cpal::Host, Box<dyn Error>> {
+    let name = match name {
+        Some(n) => n,
+        None => return Ok(cpal::default_host()),
+    };
+
+    let hosts = cpal::available_hosts();
+    match hosts.iter().find(|h| h.name().eq_ignore_ascii_case(name)) {
+        Some(id) => Ok(cpal::host_from_id(*id)?),
+        None => {
+            let available: Vec<_> = hosts.iter().map(|h| h.name().to_lowercase()).collect();
+            Err(format!(
+                "Audio host {} is not available (available hosts: {})",
+                name,
+                available.join(", ")
+            )
+            .into())
+        }
+    }
+}
+
+fn select_device(
+    devices: Vec<cpal::Device>,
+    selector: &str,
+) -> Result<cpal::Device, Box<dyn Error>> {
+    if let Ok(id) = selector.parse::<usize>() {
+        return devices
+            .into_iter()
+            .nth(id)
+            .ok_or_else(|| format!("No audio device with id {}", id).into());
+    }
+
+    let pattern = selector.to_lowercase();
+    for d in devices {
+        if d.name()?.to_lowercase().contains(&pattern) {
+            return Ok(d);
+        }
+    }
+    Err(format!("No audio device matching \"{}\"", selector).into())
+}
+
+pub fn list_devices(host: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let hosts = match host {
+        Some(_) => vec![select_host(host)?],
+        None => cpal::available_hosts()
+            .into_iter()
+            .map(cpal::host_from_id)
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    for h in hosts {
+        println!("[HOST] {}", h.id().name().to_lowercase());
+        for (i, d) in h.input_devices()?.enumerate() {
+            println!("\t[DEVICE {}] {}", i, d.name()?);
+        }
+    }
+    Ok(())
+}
+
+impl InputSource {
+    pub fn new(audio_config: &AudioConfig) -> Result<Self, Box<dyn Error>> {
+        let host = select_host(audio_config.host.as_deref())?;
+        let devices: Vec<_> = host.input_devices()?.collect();
+
+        let device = match &audio_config.device {
+            Some(selector) => select_device(devices, selector)?,
+            None => {
+                for (i, d) in devices.iter().enumerate() {
+                    println!("[DEVICE {}] {}", i, d.name()?);
+                }
+
+                let device_id: usize = prompt_default("Select Device Id", 0)?;
+                select_device(devices, &device_id.to_string())?
+            }
+        };
+
+        println!("[AUDIO DEVICE] {}", device.name()?);
+
+        let config = device.default_input_config()?;
+
+        println!("[DEFAULT AUDIO CONFIG] {:?}", config);
+
+        Ok(InputSource { device, config })
+    }
+}
+
+impl AudioSource for InputSource {
+    fn sample_rate(&self) -> u32 {
+        self.config.sample_rate().0
+    }
+
+    fn nb_channels(&self) -> u16 {
+        self.config.channels()
+    }
+
+    fn start(self: Box<Self>, mut analyzer: Analyzer) -> Result<SourceHandle, Box<dyn Error>> {
+        let err_fn = move |err| {
+            eprintln!("an error occurred on stream: {}", err);
+        };
+
+        let device = self.device;
+        let config = self.config;
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::I8 => device.build_input_stream(
+                &config.into(),
+                move |data, _: &_| analyzer.process::<i8>(data),
+                err_fn,
+                None,
+            )?,
+            cpal::SampleFormat::I16 => device.build_input_stream(
+                &config.into(),
+                move |data, _: &_| analyzer.process::<i16>(data),
+                err_fn,
+                None,
+            )?,
+            cpal::SampleFormat::I32 => device.build_input_stream(
+                &config.into(),
+                move |data, _: &_| analyzer.process::<i32>(data),
+                err_fn,
+                None,
+            )?,
+            cpal::SampleFormat::F32 => device.build_input_stream(
+                &config.into(),
+                move |data, _: &_| analyzer.process::<f32>(data),
+                err_fn,
+                None,
+            )?,
+            _ => return Err(Box::from("Unsupported sample format")),
+        };
+
+        stream.play()?;
+        Ok(SourceHandle::from_stream(stream))
+    }
+}
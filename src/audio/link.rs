@@ -0,0 +1,134 @@
+// Listener of the Ableton Link sessions on the local network. Only the tempo is followed: the
+// beat positions of a session are expressed in the clock of its peers, whose offset must be
+// measured with a peer, so the phase is still aligned on the audio onsets.
+use super::clock::{ClockSender, ClockState};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::error::Error;
+use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
+use std::thread;
+use std::time::Instant;
+
+const LINK_GROUP: Ipv4Addr = Ipv4Addr::new(224, 76, 78, 75);
+const LINK_PORT: u16 = 20808;
+const PROTOCOL_HEADER: &[u8] = b"_asdp_v\x01";
+// Message type, TTL, group id and node id
+const MESSAGE_HEADER_SIZE: usize = 12;
+const MESSAGE_ALIVE: u8 = 1;
+const MESSAGE_RESPONSE: u8 = 2;
+// Tempo (microseconds per beat), beat origin and time origin
+const TIMELINE_KEY: &[u8] = b"tmln";
+const PACKET_SIZE: usize = 512;
+
+// Tempo announced by a peer
+fn parse_tempo(packet: &[u8]) -> Option<f32> {
+    let message = packet.strip_prefix(PROTOCOL_HEADER)?;
+    let header = message.get(..MESSAGE_HEADER_SIZE)?;
+    if header[0] != MESSAGE_ALIVE && header[0] != MESSAGE_RESPONSE {
+        return None;
+    }
+
+    // Payload entries: key, size and value
+    let mut payload = &message[MESSAGE_HEADER_SIZE..];
+    while payload.len() >= 8 {
+        let size = u32::from_be_bytes(payload[4..8].try_into().ok()?) as usize;
+        let value = payload.get(8..8 + size)?;
+        if &payload[..4] == TIMELINE_KEY {
+            let micros_per_beat = i64::from_be_bytes(value.get(..8)?.try_into().ok()?);
+            return (micros_per_beat > 0).then(|| 60e6 / micros_per_beat as f32);
+        }
+        payload = &payload[8 + size..];
+    }
+    None
+}
+
+pub fn spawn(mut sender: ClockSender) -> Result<(), Box<dyn Error>> {
+    // The port is shared with the Link peers running on the same machine
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, LINK_PORT).into())?;
+    socket.join_multicast_v4(&LINK_GROUP, &Ipv4Addr::UNSPECIFIED)?;
+    let socket: UdpSocket = socket.into();
+    log::info!("Listening to the Ableton Link sessions");
+
+    thread::Builder::new().name("link".into()).spawn(move || {
+        let mut buffer = [0; PACKET_SIZE];
+        let mut bpm = 0.0;
+        loop {
+            let size = match socket.recv(&mut buffer) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("Failed to receive an Ableton Link message: {e}");
+                    return;
+                }
+            };
+            if let Some(b) = parse_tempo(&buffer[..size]) {
+                if b != bpm {
+                    log::info!("Ableton Link tempo: {b:.2} BPM");
+                    bpm = b;
+                }
+                sender.send(Some(ClockState {
+                    bpm,
+                    beat: None,
+                    horizon: 0.0,
+                    updated: Instant::now(),
+                }));
+            }
+        }
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(kind: u8, entries: &[(&[u8], Vec<u8>)]) -> Vec<u8> {
+        let mut packet = PROTOCOL_HEADER.to_vec();
+        packet.push(kind);
+        packet.extend_from_slice(&[0; MESSAGE_HEADER_SIZE - 1]);
+        for (key, value) in entries {
+            packet.extend_from_slice(key);
+            packet.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            packet.extend_from_slice(value);
+        }
+        packet
+    }
+
+    fn timeline(micros_per_beat: i64) -> Vec<u8> {
+        let mut value = micros_per_beat.to_be_bytes().to_vec();
+        value.extend_from_slice(&[0; 16]);
+        value
+    }
+
+    #[test]
+    fn tempo() {
+        let entries = [
+            (&b"sess"[..], vec![1; 8]),
+            (TIMELINE_KEY, timeline(500_000)),
+        ];
+        assert_eq!(parse_tempo(&packet(MESSAGE_ALIVE, &entries)), Some(120.0));
+        assert_eq!(
+            parse_tempo(&packet(MESSAGE_RESPONSE, &entries)),
+            Some(120.0)
+        );
+        // Other message types
+        assert_eq!(parse_tempo(&packet(3, &entries)), None);
+    }
+
+    #[test]
+    fn invalid_packets() {
+        let valid = packet(MESSAGE_ALIVE, &[(TIMELINE_KEY, timeline(500_000))]);
+        for p in [
+            vec![],
+            valid[..PROTOCOL_HEADER.len() + 4].to_vec(),
+            valid[..valid.len() - 20].to_vec(),
+            [b"_asdp_v\x02", &valid[PROTOCOL_HEADER.len()..]].concat(),
+            packet(MESSAGE_ALIVE, &[(TIMELINE_KEY, vec![0; 4])]),
+            packet(MESSAGE_ALIVE, &[(&b"sess"[..], vec![1; 8])]),
+            packet(MESSAGE_ALIVE, &[(TIMELINE_KEY, timeline(0))]),
+            packet(MESSAGE_ALIVE, &[(TIMELINE_KEY, timeline(-500_000))]),
+        ] {
+            assert_eq!(parse_tempo(&p), None, "{p:?}");
+        }
+    }
+}
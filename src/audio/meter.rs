@@ -0,0 +1,179 @@
+use super::Data;
+use crossterm::{cursor, terminal, ExecutableCommand};
+use rtrb::{Consumer, Producer};
+use std::io::{stdout, Write};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Time between two polls of the analysis frames
+const POLL_PERIOD: Duration = Duration::from_millis(10);
+
+// Consumer of the analysis frames, running outside of the audio thread
+pub trait Meter: Send {
+    fn update(&mut self, data: &Data);
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum MeterKind {
+    // Gains printed in the terminal
+    #[default]
+    Terminal,
+    // Gains sent to the logs
+    Log,
+    None,
+}
+
+impl MeterKind {
+    pub fn meter(&self) -> Box<dyn Meter> {
+        match self {
+            MeterKind::Terminal => Box::new(TerminalMeter::new()),
+            MeterKind::Log => Box::new(LogMeter),
+            MeterKind::None => Box::new(SilentMeter),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TerminalMeter {
+    nb_lines: u16,
+}
+
+impl TerminalMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn print(&mut self, data: &Data) -> std::io::Result<()> {
+        let mut stdout = stdout();
+        if self.nb_lines > 0 {
+            stdout.execute(cursor::MoveUp(self.nb_lines))?;
+            stdout.execute(terminal::Clear(terminal::ClearType::FromCursorDown))?;
+        }
+        for (i, g) in data.gain.iter().enumerate() {
+            if *g == f32::MIN {
+                writeln!(stdout, "audio_channel[{}]: -INF", i)?;
+            } else {
+                writeln!(stdout, "audio_channel[{}]: {}", i, g)?;
+            }
+        }
+        self.nb_lines = data.gain.len() as u16;
+        Ok(())
+    }
+}
+
+impl Meter for TerminalMeter {
+    fn update(&mut self, data: &Data) {
+        if let Err(e) = self.print(data) {
+            log::warn!("Failed to print the audio meter: {e}");
+        }
+    }
+}
+
+pub struct LogMeter;
+
+impl Meter for LogMeter {
+    fn update(&mut self, data: &Data) {
+        log::info!(
+            "frame: {}, gain: {:?}, onset: {}, bpm: {:.1}",
+            data.frame,
+            data.gain,
+            data.onset,
+            data.bpm
+        );
+    }
+}
+
+pub struct SilentMeter;
+
+impl Meter for SilentMeter {
+    fn update(&mut self, _data: &Data) {}
+}
+
+// Records every analysis frame
+#[derive(Clone, Default)]
+pub struct ProbeMeter {
+    frames: Arc<Mutex<Vec<Data>>>,
+}
+
+impl ProbeMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn frames(&self) -> Vec<Data> {
+        self.frames.lock().unwrap().clone()
+    }
+}
+
+impl Meter for ProbeMeter {
+    fn update(&mut self, data: &Data) {
+        self.frames.lock().unwrap().push(data.clone());
+    }
+}
+
+// Feeds the meter with the frames pushed by the audio thread until it stops, returning them to
+// the pool of the audio thread
+pub fn spawn(
+    mut consumer: Consumer<Data>,
+    mut pool: Producer<Data>,
+    mut meter: Box<dyn Meter>,
+) -> JoinHandle<()> {
+    thread::Builder::new()
+        .name("audio_meter".into())
+        .spawn(move || loop {
+            // Checked first, not to miss the last frames pushed
+            let abandoned = consumer.is_abandoned();
+            while let Ok(data) = consumer.pop() {
+                meter.update(&data);
+                let _ = pool.push(data);
+            }
+            if abandoned {
+                break;
+            }
+            thread::sleep(POLL_PERIOD);
+        })
+        .expect("Failed to spawn the audio meter thread")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rtrb::RingBuffer;
+
+    #[test]
+    fn probe_records_frames() {
+        let probe = ProbeMeter::new();
+        let mut meter = probe.clone();
+        for i in 0..3 {
+            let mut data = Data::new(2);
+            data.frame = i;
+            meter.update(&data);
+        }
+        let frames: Vec<_> = probe.frames().iter().map(|d| d.frame).collect();
+        assert_eq!(frames, [0, 1, 2]);
+    }
+
+    #[test]
+    fn spawn_returns_frames_to_pool() {
+        let (mut producer, consumer) = RingBuffer::new(4);
+        let (pool, mut pool_consumer) = RingBuffer::new(4);
+        let probe = ProbeMeter::new();
+        let thread = spawn(consumer, pool, Box::new(probe.clone()));
+        for i in 0..4 {
+            let mut data = Data::new(1);
+            data.frame = i;
+            producer.push(data).unwrap();
+        }
+        drop(producer);
+        thread.join().unwrap();
+
+        let frames: Vec<_> = probe.frames().iter().map(|d| d.frame).collect();
+        assert_eq!(frames, [0, 1, 2, 3]);
+        let mut returned = vec![];
+        while let Ok(data) = pool_consumer.pop() {
+            returned.push(data.frame);
+        }
+        assert_eq!(returned, [0, 1, 2, 3]);
+    }
+}
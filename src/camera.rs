@@ -40,3 +40,18 @@ impl From<Camera> for CameraUniform {
         }
     }
 }
+
+impl CameraUniform {
+    // Applies the transform from the clip space of the canvas to the one of the display
+    pub fn crop(mut self, scale: [f32; 2], offset: [f32; 2]) -> Self {
+        #[rustfmt::skip]
+        let crop = cgmath::Matrix4::new(
+            scale[0], 0.0, 0.0, 0.0,
+            0.0, scale[1], 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            offset[0], offset[1], 0.0, 1.0,
+        );
+        self.view_proj = (crop * cgmath::Matrix4::from(self.view_proj)).into();
+        self
+    }
+}
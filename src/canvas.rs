@@ -0,0 +1,243 @@
+use crate::config::{CanvasConfig, RegionConfig};
+use crate::display::DisplayError;
+
+// Region of the canvas rendered by a display, in canvas pixels
+#[derive(Clone, Copy, Debug)]
+pub struct Span {
+    pub canvas: [u32; 2],
+    pub offset: [f32; 2],
+    pub size: [f32; 2],
+    // Widths of the edges overlapping another display (left, top, right, bottom)
+    pub blend: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ViewportUniform {
+    blend: [f32; 4],
+    offset: [f32; 2],
+    size: [f32; 2],
+    // Size of the display, in pixels
+    resolution: [f32; 2],
+    clip_scale: [f32; 2],
+    clip_offset: [f32; 2],
+    _padding: [f32; 2],
+}
+
+impl Span {
+    // The whole canvas is rendered by the display
+    pub fn full(size: [u32; 2]) -> Self {
+        Span {
+            canvas: size,
+            offset: [0.0; 2],
+            size: size.map(|s| s as f32),
+            blend: [0.0; 4],
+        }
+    }
+
+    // Transform from the clip space of the canvas to the one of the display
+    pub fn clip_transform(&self) -> ([f32; 2], [f32; 2]) {
+        let canvas = self.canvas.map(|s| s as f32);
+        let scale = [canvas[0] / self.size[0], canvas[1] / self.size[1]];
+        // The y axis of the clip space points up
+        let offset = [
+            (canvas[0] - 2.0 * self.offset[0]) / self.size[0] - 1.0,
+            1.0 - (canvas[1] - 2.0 * self.offset[1]) / self.size[1],
+        ];
+        (scale, offset)
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.canvas[0] as f32 / self.canvas[1] as f32
+    }
+
+    pub fn viewport(&self, resolution: [u32; 2]) -> ViewportUniform {
+        let (clip_scale, clip_offset) = self.clip_transform();
+        ViewportUniform {
+            blend: self.blend,
+            offset: self.offset,
+            size: self.size,
+            resolution: resolution.map(|r| r as f32),
+            clip_scale,
+            clip_offset,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+// The edges are blended where the regions of two displays overlap on their whole height or width.
+// Without canvas, each display renders its whole window.
+pub fn spans(
+    canvas: Option<&CanvasConfig>,
+    regions: &[Option<RegionConfig>],
+) -> Result<Vec<Option<Span>>, DisplayError> {
+    let Some(canvas) = canvas else {
+        if regions.iter().any(Option::is_some) {
+            return Err(DisplayError::InvalidRegion(
+                "the display regions require a canvas".to_string(),
+            ));
+        }
+        return Ok(vec![None; regions.len()]);
+    };
+
+    let full = RegionConfig {
+        x: 0,
+        y: 0,
+        width: canvas.width,
+        height: canvas.height,
+    };
+    let regions: Vec<_> = regions.iter().map(|r| r.unwrap_or(full)).collect();
+    for r in &regions {
+        if r.width == 0
+            || r.height == 0
+            || r.x.checked_add(r.width).is_none_or(|e| e > canvas.width)
+            || r.y.checked_add(r.height).is_none_or(|e| e > canvas.height)
+        {
+            return Err(DisplayError::InvalidRegion(format!(
+                "the region {}x{}+{}+{} is empty or outside the {}x{} canvas",
+                r.width, r.height, r.x, r.y, canvas.width, canvas.height
+            )));
+        }
+    }
+
+    let spans = regions
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let [x0, y0, x1, y1] = [r.x, r.y, r.x + r.width, r.y + r.height];
+            let mut blend = [0u32; 4];
+            for (j, o) in regions.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                // Intersection of the regions
+                let [ix0, iy0] = [x0.max(o.x), y0.max(o.y)];
+                let [ix1, iy1] = [x1.min(o.x + o.width), y1.min(o.y + o.height)];
+                if ix0 >= ix1 || iy0 >= iy1 {
+                    continue;
+                }
+                // Both regions have the same height
+                if o.y == y0 && o.y + o.height == y1 {
+                    if ix0 == x0 && ix1 < x1 {
+                        blend[0] = blend[0].max(ix1 - x0);
+                    } else if ix1 == x1 && ix0 > x0 {
+                        blend[2] = blend[2].max(x1 - ix0);
+                    }
+                }
+                // or the same width
+                if o.x == x0 && o.x + o.width == x1 {
+                    if iy0 == y0 && iy1 < y1 {
+                        blend[1] = blend[1].max(iy1 - y0);
+                    } else if iy1 == y1 && iy0 > y0 {
+                        blend[3] = blend[3].max(y1 - iy0);
+                    }
+                }
+            }
+            Some(Span {
+                canvas: [canvas.width, canvas.height],
+                offset: [r.x as f32, r.y as f32],
+                size: [r.width as f32, r.height as f32],
+                blend: blend.map(|b| b as f32),
+            })
+        })
+        .collect();
+    Ok(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CANVAS: CanvasConfig = CanvasConfig {
+        width: 3640,
+        height: 1080,
+    };
+
+    fn region(x: u32, y: u32, width: u32, height: u32) -> Option<RegionConfig> {
+        Some(RegionConfig {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    fn blends(regions: &[Option<RegionConfig>]) -> Vec<[f32; 4]> {
+        spans(Some(&CANVAS), regions)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.unwrap().blend)
+            .collect()
+    }
+
+    #[test]
+    fn without_canvas() {
+        let spans = spans(None, &[None, None]).unwrap();
+        assert!(spans.iter().all(Option::is_none));
+        assert!(super::spans(None, &[None, region(0, 0, 10, 10)]).is_err());
+    }
+
+    #[test]
+    fn invalid_regions() {
+        for r in [
+            region(0, 0, 0, 1080),
+            region(0, 0, 1920, 0),
+            region(1721, 0, 1920, 1080),
+            region(0, 1, 1920, 1080),
+            region(u32::MAX, 0, 1, 1080),
+            region(0, 1, 1920, u32::MAX),
+        ] {
+            assert!(spans(Some(&CANVAS), &[region(0, 0, 1920, 1080), r]).is_err());
+        }
+    }
+
+    #[test]
+    fn side_by_side_overlap() {
+        let blends = blends(&[region(0, 0, 1920, 1080), region(1720, 0, 1920, 1080)]);
+        assert_eq!(blends, [[0.0, 0.0, 200.0, 0.0], [200.0, 0.0, 0.0, 0.0]]);
+    }
+
+    #[test]
+    fn stacked_overlap() {
+        let blends = blends(&[region(0, 0, 1000, 600), region(0, 500, 1000, 580)]);
+        assert_eq!(blends, [[0.0, 0.0, 0.0, 100.0], [0.0, 100.0, 0.0, 0.0]]);
+    }
+
+    #[test]
+    fn no_blend() {
+        // Gap between the regions
+        let gap = blends(&[region(0, 0, 1000, 1080), region(2000, 0, 1000, 1080)]);
+        // Adjacent regions
+        let adjacent = blends(&[region(0, 0, 1000, 1080), region(1000, 0, 1000, 1080)]);
+        // Overlap on a part of the height
+        let partial = blends(&[region(0, 0, 1000, 1080), region(900, 100, 1000, 900)]);
+        // Region inside another one
+        let inside = blends(&[None, region(100, 100, 1000, 500)]);
+        for b in [gap, adjacent, partial, inside] {
+            assert_eq!(b, [[0.0; 4]; 2]);
+        }
+    }
+
+    #[test]
+    fn widest_overlap() {
+        let blends = blends(&[
+            region(0, 0, 1920, 1080),
+            region(1820, 0, 1000, 1080),
+            region(1720, 0, 1920, 1080),
+        ]);
+        assert_eq!(blends[0], [0.0, 0.0, 200.0, 0.0]);
+    }
+
+    #[test]
+    fn clip_transform() {
+        assert_eq!(
+            Span::full([1920, 1080]).clip_transform(),
+            ([1.0, 1.0], [0.0, 0.0])
+        );
+        let spans = spans(Some(&CANVAS), &[region(0, 0, 1820, 1080)]).unwrap();
+        let (scale, offset) = spans[0].unwrap().clip_transform();
+        // The left half of the canvas fills the display
+        assert_eq!(scale, [2.0, 1.0]);
+        assert_eq!(offset, [1.0, 0.0]);
+    }
+}
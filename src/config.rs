@@ -0,0 +1,346 @@
+use crate::control::{Param, Trigger};
+use crate::keymap::Action;
+use crate::window::WindowConfig;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+use winit::event::VirtualKeyCode;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read config file [{}: {}]\n\t{0}", file!(), line!())]
+    Reading(#[from] std::io::Error),
+    #[error("Failed to parse config file [{}: {}]\n\t{0}", file!(), line!())]
+    Parsing(#[from] toml::de::Error),
+    #[error("Failed to write config file [{}: {}]\n\t{0}", file!(), line!())]
+    Writing(std::io::Error),
+    #[error("Failed to serialize config file [{}: {}]\n\t{0}", file!(), line!())]
+    Serializing(#[from] toml::ser::Error),
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    // Default envelope of the audio bands
+    #[serde(default)]
+    pub envelope: EnvelopeConfig,
+    // Audio bands, replacing the equal-octave split when not empty
+    #[serde(default, rename = "band")]
+    pub bands: Vec<BandConfig>,
+    // Keys of the performer actions, replacing the default ones
+    #[serde(default)]
+    pub keys: HashMap<Action, Vec<VirtualKeyCode>>,
+    // Output displays, replacing the window given on the command line when not empty
+    #[serde(default, rename = "display")]
+    pub displays: Vec<DisplayConfig>,
+    // The displays make the same random choices, so that the displays of the same show display
+    // the same scene
+    #[serde(default)]
+    pub sync: bool,
+    // Canvas spread across the displays
+    pub canvas: Option<CanvasConfig>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs_err::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BandConfig {
+    // e.g. "40-120Hz" or "5k-16kHz"
+    pub range: FreqRange,
+    // Weighting of the band gain
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+    // Exponential smoothing of the band gain, from 0 (none) to 1 (excluded)
+    #[serde(default, deserialize_with = "deserialize_smoothing")]
+    pub smoothing: f32,
+    // Duration of the statistics window, in seconds
+    #[serde(default, deserialize_with = "deserialize_stat_window")]
+    pub stat_window: Option<f32>,
+    // Override the default envelope
+    pub attack: Option<f32>,
+    pub release: Option<f32>,
+    pub hold: Option<f32>,
+}
+
+fn default_gain() -> f32 {
+    1.0
+}
+
+fn deserialize_smoothing<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    let smoothing = f32::deserialize(deserializer)?;
+    if !(0.0..1.0).contains(&smoothing) {
+        return Err(D::Error::custom(format!(
+            "invalid smoothing {smoothing} (expected a value in [0, 1))"
+        )));
+    }
+    Ok(smoothing)
+}
+
+fn deserialize_stat_window<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<f32>, D::Error> {
+    let stat_window = f32::deserialize(deserializer)?;
+    if !(stat_window.is_finite() && stat_window > 0.0) {
+        return Err(D::Error::custom(format!(
+            "invalid statistics window {stat_window} (expected a positive duration)"
+        )));
+    }
+    Ok(Some(stat_window))
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DisplayConfig {
+    // The show given on the command line by default
+    pub show: Option<String>,
+    // Show of the post-processing shader, the displayed show by default
+    pub post: Option<String>,
+    #[serde(default)]
+    pub camera: CameraConfig,
+    #[serde(default)]
+    pub window: WindowConfig,
+    // Region of the canvas rendered by the display, the whole canvas by default
+    pub region: Option<RegionConfig>,
+}
+
+// Virtual resolution of the canvas, in pixels
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CanvasConfig {
+    pub width: u32,
+    pub height: u32,
+}
+
+// Position and size in the canvas, in pixels
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RegionConfig {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(deny_unknown_fields, default)]
+pub struct CameraConfig {
+    pub eye: [f32; 3],
+    pub target: [f32; 3],
+    // Vertical field of view, in degrees
+    pub fovy: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig {
+            eye: [0.0, 0.0, 5.0],
+            target: [0.0, 0.0, 0.0],
+            fovy: 50.0,
+        }
+    }
+}
+
+// Envelope follower of the band levels, durations in seconds
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(deny_unknown_fields, default)]
+pub struct EnvelopeConfig {
+    pub attack: f32,
+    pub release: f32,
+    // Duration during which the peak is held before it is released
+    pub hold: f32,
+}
+
+impl Default for EnvelopeConfig {
+    fn default() -> Self {
+        EnvelopeConfig {
+            attack: 0.01,
+            release: 0.3,
+            hold: 0.5,
+        }
+    }
+}
+
+// Frequency range, in Hz
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(try_from = "String")]
+pub struct FreqRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl TryFrom<String> for FreqRange {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let invalid =
+            || format!("invalid frequency range {s} (expected e.g. 40-120Hz or 5k-16kHz)");
+        let (min, max) = s.split_once('-').ok_or_else(invalid)?;
+        let min = parse_freq(min).ok_or_else(invalid)?;
+        let max = parse_freq(max).ok_or_else(invalid)?;
+        if min <= 0.0 || min >= max {
+            return Err(invalid());
+        }
+        Ok(FreqRange { min, max })
+    }
+}
+
+// Frequency in Hz, with an optional k prefix and Hz unit
+fn parse_freq(s: &str) -> Option<f32> {
+    let s = s.trim().to_lowercase();
+    let s = s.strip_suffix("hz").unwrap_or(&s).trim_end();
+    match s.strip_suffix('k') {
+        Some(k) => k.parse::<f32>().ok().map(|f| 1000.0 * f),
+        None => s.parse().ok(),
+    }
+}
+
+// Noise floors measured by the calibration
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct NoiseProfile {
+    // Noise floor of each band, in dB
+    pub floors: Vec<f32>,
+}
+
+impl NoiseProfile {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs_err::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        let content = toml::to_string(self)?;
+        fs_err::write(path, content).map_err(ConfigError::Writing)
+    }
+}
+
+// Mapping of the MIDI messages to the performer controls
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct MidiMapping {
+    // Notes activating a pipeline
+    #[serde(default, rename = "note")]
+    pub notes: Vec<NoteMapping>,
+    // Control changes setting a parameter
+    #[serde(default, rename = "cc")]
+    pub ccs: Vec<CcMapping>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct NoteMapping {
+    // MIDI channel, from 1 to 16, any channel when omitted
+    pub channel: Option<u8>,
+    pub note: u8,
+    pub trigger: Trigger,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CcMapping {
+    // MIDI channel, from 1 to 16, any channel when omitted
+    pub channel: Option<u8>,
+    pub cc: u8,
+    pub param: Param,
+}
+
+impl MidiMapping {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs_err::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        let content = toml::to_string(self)?;
+        fs_err::write(path, content).map_err(ConfigError::Writing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(s: &str) -> Result<(f32, f32), String> {
+        FreqRange::try_from(s.to_string()).map(|r| (r.min, r.max))
+    }
+
+    #[test]
+    fn freq_range() {
+        assert_eq!(range("40-120Hz"), Ok((40.0, 120.0)));
+        assert_eq!(range("5k-16kHz"), Ok((5000.0, 16000.0)));
+        assert_eq!(range("1.5k - 2K hz"), Ok((1500.0, 2000.0)));
+        assert_eq!(range("20-200"), Ok((20.0, 200.0)));
+        for s in [
+            "120-40Hz",
+            "0-100Hz",
+            "100-100Hz",
+            "40Hz",
+            "40-x",
+            "-40-120Hz",
+            "",
+        ] {
+            assert!(range(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn config() {
+        let config: Config = toml::from_str(
+            r#"
+            [envelope]
+            attack = 0.05
+
+            [[band]]
+            range = "40-120Hz"
+            smoothing = 0.5
+            stat_window = 2.0
+
+            [[band]]
+            range = "5k-16kHz"
+            gain = 2.0
+            release = 1.0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.envelope.attack, 0.05);
+        assert_eq!(config.envelope.release, EnvelopeConfig::default().release);
+        assert_eq!(config.bands.len(), 2);
+        assert_eq!(config.bands[0].gain, 1.0);
+        assert_eq!(config.bands[0].smoothing, 0.5);
+        assert_eq!(config.bands[0].stat_window, Some(2.0));
+        assert_eq!(config.bands[1].stat_window, None);
+        assert_eq!(config.bands[1].range.min, 5000.0);
+        assert_eq!(config.bands[1].gain, 2.0);
+        assert_eq!(config.bands[1].release, Some(1.0));
+        assert!(config.displays.is_empty());
+        assert!(!config.sync);
+    }
+
+    #[test]
+    fn invalid_config() {
+        for s in [
+            "[[band]]\nrange = \"120-40Hz\"",
+            "[[band]]\ngain = 1.0",
+            "[[band]]\nrange = \"40-120Hz\"\nweight = 1.0",
+            "[envelope]\ndecay = 1.0",
+            "[[band]]\nrange = \"40-120Hz\"\nsmoothing = -0.1",
+            "[[band]]\nrange = \"40-120Hz\"\nsmoothing = 1.0",
+            "[[band]]\nrange = \"40-120Hz\"\nsmoothing = nan",
+            "[[band]]\nrange = \"40-120Hz\"\nstat_window = inf",
+            "[[band]]\nrange = \"40-120Hz\"\nstat_window = nan",
+            "[[band]]\nrange = \"40-120Hz\"\nstat_window = 0.0",
+            "[[band]]\nrange = \"40-120Hz\"\nstat_window = -2.0",
+            "bands = []",
+        ] {
+            assert!(toml::from_str::<Config>(s).is_err(), "{s}");
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+// Dynamic pipelines of the visual show that can be activated on demand
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Trigger {
+    #[serde(rename = "noise-3d")]
+    Noise3d,
+    Full,
+    Disk,
+    #[serde(rename = "wf-3d")]
+    Wf3d,
+}
+
+impl Trigger {
+    pub const ALL: [Trigger; 4] = [
+        Trigger::Noise3d,
+        Trigger::Full,
+        Trigger::Disk,
+        Trigger::Wf3d,
+    ];
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Trigger::Noise3d => "noise-3d",
+                Trigger::Full => "full",
+                Trigger::Disk => "disk",
+                Trigger::Wf3d => "wf-3d",
+            }
+        )
+    }
+}
+
+impl FromStr for Trigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Trigger::ALL
+            .into_iter()
+            .find(|t| t.to_string() == s)
+            .ok_or_else(|| format!("unknown trigger {s}"))
+    }
+}
+
+// Continuous parameters of the visual show
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Param {
+    // Speed of the animations
+    Speed,
+    // Duration of the activated pipelines
+    Duration,
+    // Strength of the post-processing
+    Intensity,
+    // Hue rotation of the post-processing
+    Hue,
+}
+
+impl Param {
+    pub const ALL: [Param; 4] = [Param::Speed, Param::Duration, Param::Intensity, Param::Hue];
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Param::Speed => "speed",
+                Param::Duration => "duration",
+                Param::Intensity => "intensity",
+                Param::Hue => "hue",
+            }
+        )
+    }
+}
+
+impl FromStr for Param {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Param::ALL
+            .into_iter()
+            .find(|p| p.to_string() == s)
+            .ok_or_else(|| format!("unknown parameter {s}"))
+    }
+}
+
+// Values of the parameters between 0 and 1, uploaded to the shaders
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Params {
+    speed: f32,
+    duration: f32,
+    intensity: f32,
+    hue: f32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            speed: 0.5,
+            duration: 0.5,
+            intensity: 0.5,
+            hue: 0.5,
+        }
+    }
+}
+
+impl Params {
+    pub fn get(&self, param: Param) -> f32 {
+        match param {
+            Param::Speed => self.speed,
+            Param::Duration => self.duration,
+            Param::Intensity => self.intensity,
+            Param::Hue => self.hue,
+        }
+    }
+
+    pub fn set(&mut self, param: Param, value: f32) {
+        let value = value.clamp(0.0, 1.0);
+        match param {
+            Param::Speed => self.speed = value,
+            Param::Duration => self.duration = value,
+            Param::Intensity => self.intensity = value,
+            Param::Hue => self.hue = value,
+        }
+    }
+
+    // Factor applied to the default value, from 0.5 (0) to 2 (1), 1 by default
+    pub fn multiplier(&self, param: Param) -> f32 {
+        4f32.powf(self.get(param) - 0.5)
+    }
+}
+
+// Performer controls, shared by all the displays
+#[derive(Clone, Debug, Default)]
+pub struct Controls {
+    // Pipelines activated since the previous frame
+    pub triggers: Vec<Trigger>,
+    pub params: Params,
+    // Visual show and color palette selected since the previous frame
+    pub show: Option<String>,
+    pub palette: Option<usize>,
+    pub next_palette: bool,
+    // Only black is displayed
+    pub blackout: bool,
+    // Only the background and the logo are displayed
+    pub logo_only: bool,
+    // Selected band, modulo the number of active pipelines
+    pub band: usize,
+    // Pipeline forced into the selected band since the previous frame
+    pub force: Option<Trigger>,
+    // Activate the pipeline of the selected band
+    pub activate: bool,
+    // Offset subtracted from the activation thresholds of the gains
+    pub sensitivity: f32,
+}
+
+impl Controls {
+    // Clears the events of the previous frame
+    pub fn clear(&mut self) {
+        self.triggers.clear();
+        self.show = None;
+        self.palette = None;
+        self.next_palette = false;
+        self.force = None;
+        self.activate = false;
+    }
+}
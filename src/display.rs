@@ -1,17 +1,26 @@
 use crate::audio;
 use crate::camera::{Camera, CameraUniform};
+use crate::canvas::Span;
+use crate::config::{CanvasConfig, DisplayConfig};
+use crate::control::{Controls, Params};
 use crate::instance::Instance;
+use crate::overlay::Overlay;
 use crate::pipeline;
+use crate::show::{Show, ShowLibrary};
+use crate::target::RenderTarget;
 use crate::texture;
-use crate::texture::{Texture, TextureError};
+use crate::texture::{AudioTexture, Texture, TextureError};
 use crate::vs_0;
+use std::collections::BTreeMap;
 use std::iter;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::rc::Rc;
 use thiserror::Error;
 use wgpu::util::DeviceExt;
 use winit::window::Window;
 
+// Number of spectrums kept in the spectrum texture
+const SPECTRUM_HISTORY: u32 = 128;
+
 #[derive(Error, Debug)]
 pub enum DisplayError {
     #[error("Failed to create a pipeline [{}: {}]\n\t{0}", file!(), line!())]
@@ -22,17 +31,99 @@ pub enum DisplayError {
     DeviceRequest(#[from] wgpu::RequestDeviceError),
     #[error("Failed to load a texture [{}: {}]\n\t{0}", file!(), line!())]
     TextureLoad(#[from] TextureError),
+    #[error("Invalid display region [{}: {}]\n\t{0}", file!(), line!())]
+    InvalidRegion(String),
+    #[error("Failed to select a show [{}: {}]\n\t{0}", file!(), line!())]
+    ShowSelection(#[from] crate::show::ShowError),
+    #[error("Failed to read a frame back [{}: {}]\n\t{0}", file!(), line!())]
+    Readback(#[from] wgpu::BufferAsyncError),
+    #[error("The frames of a window cannot be read back [{}: {}]", file!(), line!())]
+    WindowReadback,
 }
 
-pub struct Display {
-    surface: wgpu::Surface,
+pub struct OutputConfig {
+    pub displays: Vec<DisplayConfig>,
+    // Canvas spread across the displays, each display rendering its region
+    pub canvas: Option<CanvasConfig>,
+    // The displays make the same random choices, always the case with a canvas
+    pub sync: bool,
+    // Shows that can be displayed, by name
+    pub shows: ShowLibrary,
+    // Software adapter, for the machines without GPU
+    pub force_fallback_adapter: bool,
+}
+
+// Device shared by the displays
+pub struct Gpu {
+    adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
-    pub size: winit::dpi::PhysicalSize<u32>,
-    window: Window,
+}
+
+impl Gpu {
+    // Returns the surfaces of the windows, the adapter being compatible with the first one
+    pub async fn new(
+        windows: &[Window],
+        force_fallback_adapter: bool,
+    ) -> Result<(Self, Vec<wgpu::Surface>), DisplayError> {
+        // The instance is a handle to our GPU
+        // BackendBit::PRIMARY => Vulkan + Metal + DX12 + Browser WebGPU
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+
+        // # Safety
+        //
+        // The surfaces need to live as long as the windows that created them.
+        // Each Display is dropped before its window so this should be safe.
+        let surfaces: Vec<_> = windows
+            .iter()
+            .map(|w| unsafe { instance.create_surface(w) })
+            .collect();
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: surfaces.first(),
+                force_fallback_adapter,
+            })
+            .await
+            .ok_or(DisplayError::AdapterRequest)?;
+
+        let info = adapter.get_info();
+        println!("[ADAPTER] {} ({:?})", info.name, info.backend);
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features: wgpu::Features::empty(),
+                    // WebGL doesn't support all of wgpu's features, so if
+                    // we're building for the web we'll have to disable some.
+                    limits: wgpu::Limits::default(),
+                },
+                None, // Trace path
+            )
+            .await?;
+
+        Ok((
+            Gpu {
+                adapter,
+                device,
+                queue,
+            },
+            surfaces,
+        ))
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+}
 
-    start_time: Instant,
+pub struct Display {
+    gpu: Rc<Gpu>,
+    target: RenderTarget,
+    config: wgpu::SurfaceConfiguration,
+    pub size: [u32; 2],
 
     // Pipelines
     pipeline_groups: Vec<pipeline::PipelineGroup>,
@@ -44,85 +135,127 @@ pub struct Display {
     // Textures
     depth_texture: Texture,
     framebuffer: Texture,
+    spectrum_texture: AudioTexture,
+    waveform_texture: AudioTexture,
 
     // Buffers
     audio_buffer: wgpu::Buffer,
     time_buffer: wgpu::Buffer,
     size_buffer: wgpu::Buffer,
+    params_buffer: wgpu::Buffer,
+    viewport_buffer: wgpu::Buffer,
     camera_buffer: wgpu::Buffer,
 
     // Bind groups
     bind_groups: Vec<wgpu::BindGroup>,
-    texture_bind_group_layout: wgpu::BindGroupLayout,
+    bind_group_layouts: Vec<wgpu::BindGroupLayout>,
 
     // Audio
     audio_data: audio::Data,
 
     // State
     vs_0_state: vs_0::State,
+    // Show of the post-processing shader, the displayed show when None
+    post: Option<Rc<Show>>,
+    seed: Option<u64>,
+    // Region of the canvas, the whole window when None
+    span: Option<Span>,
+    blackout: bool,
+    // Errors of the reloaded shaders, by file name
+    shader_errors: BTreeMap<String, String>,
+    overlay: Overlay,
 }
 
-impl Display {
-    pub async fn new(window: Window, show: vs_0::Show) -> Result<Self, DisplayError> {
-        let size = window.inner_size();
-
-        // The instance is a handle to our GPU
-        // BackendBit::PRIMARY => Vulkan + Metal + DX12 + Browser WebGPU
-        let instance = wgpu::Instance::new(wgpu::Backends::all());
-
-        // # Safety
-        //
-        // The surface needs to live as long as the window that created it.
-        // State owns the window so this should be safe.
-        let surface = unsafe { instance.create_surface(&window) };
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .ok_or(DisplayError::AdapterRequest)?;
+// Creates the pipelines of a visual show
+fn create_pipelines(
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    show: Rc<Show>,
+    post: &Show,
+    nb_bands: usize,
+    seed: Option<u64>,
+) -> Result<
+    (
+        Vec<pipeline::PipelineGroup>,
+        pipeline::PipelinePost,
+        vs_0::State,
+    ),
+    DisplayError,
+> {
+    // Create the pipeline group
+    let bind_group_indices_0 = pipeline::GROUP_0_BIND_GROUPS.to_vec();
+    let mut pipeline_group_0 =
+        pipeline::PipelineGroup::new_0(bind_group_layouts, bind_group_indices_0, device);
+
+    // Create the pipelines in pipeline group 0
+    let vs_0_state = vs_0::State::new(&mut pipeline_group_0, device, show, nb_bands, seed)?;
+
+    let pipeline_groups = vec![pipeline_group_0];
+
+    // Create postpipeline
+    let bind_group_indices_post = pipeline::POST_BIND_GROUPS.to_vec();
+
+    let pipeline_post = pipeline::PipelinePost::new(
+        bind_group_layouts,
+        bind_group_indices_post,
+        device,
+        config,
+        &post.post,
+        &post.post_file,
+    )?;
+
+    Ok((pipeline_groups, pipeline_post, vs_0_state))
+}
 
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::empty(),
-                    // WebGL doesn't support all of wgpu's features, so if
-                    // we're building for the web we'll have to disable some.
-                    limits: wgpu::Limits::default(),
-                },
-                None, // Trace path
-            )
-            .await?;
+impl Display {
+    // The seed is shared by the synchronized displays
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        gpu: Rc<Gpu>,
+        target: RenderTarget,
+        size: [u32; 2],
+        display_config: &DisplayConfig,
+        shows: &ShowLibrary,
+        nb_bands: usize,
+        seed: Option<u64>,
+        span: Option<Span>,
+    ) -> Result<Self, DisplayError> {
+        let current_span = span.unwrap_or_else(|| Span::full(size));
+        let device = &gpu.device;
+        let queue = &gpu.queue;
+        let show = shows.get(display_config.show.as_deref().unwrap_or_default())?;
+        let post = display_config
+            .post
+            .as_deref()
+            .map(|p| shows.get(p))
+            .transpose()?;
 
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_supported_formats(&adapter)[0],
-            width: size.width,
-            height: size.height,
+            format: target.format(&gpu.adapter),
+            width: size[0],
+            height: size[1],
             present_mode: wgpu::PresentMode::Fifo,
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
         };
-        surface.configure(&device, &config);
+        target.configure(device, &config);
 
         // Camera bind group
         let camera = Camera {
-            // +z is out of the screen
-            eye: (0.0, 0.0, 5.0).into(),
-            // have it look at the origin
-            target: (0.0, 0.0, 0.0).into(),
+            // +z is out of the screen by default
+            eye: display_config.camera.eye.into(),
+            target: display_config.camera.target.into(),
             // which way is "up"
             up: cgmath::Vector3::unit_y(),
-            aspect: config.width as f32 / config.height as f32,
-            fovy: 50.0,
+            aspect: current_span.aspect(),
+            fovy: display_config.camera.fovy,
             znear: 0.1,
             zfar: 100.0,
         };
 
-        let camera_uniform: CameraUniform = camera.into();
+        let (clip_scale, clip_offset) = current_span.clip_transform();
+        let camera_uniform = CameraUniform::from(camera).crop(clip_scale, clip_offset);
         let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("view_proj_buffer"),
             contents: bytemuck::cast_slice(&[camera_uniform]),
@@ -154,17 +287,15 @@ impl Display {
         });
 
         // Audio bindings
-        let audio_data = audio::Data::new();
+        let audio_data = audio::Data::new(nb_bands);
         let audio_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("audio_buffer"),
-            contents: bytemuck::cast_slice(&[audio_data]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            contents: &audio_data.to_bytes(),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
         });
 
         // Time bindings
-        let start_time = Instant::now();
-        let time = start_time.elapsed().as_secs_f32();
-
+        let time: f32 = 0.0;
         let time_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("time_buffer"),
             contents: bytemuck::cast_slice(&[time]),
@@ -172,13 +303,37 @@ impl Display {
         });
 
         // Size bindings
-        let size_data: [u32; 2] = [size.width, size.height];
+        // Size of the canvas
         let size_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("size_buffer"),
-            contents: bytemuck::cast_slice(&[size_data]),
+            contents: bytemuck::cast_slice(&[current_span.canvas]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        // Params bindings
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("params_buffer"),
+            contents: bytemuck::cast_slice(&[Params::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Viewport bindings
+        let viewport = current_span.viewport(size);
+        let viewport_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("viewport_buffer"),
+            contents: bytemuck::cast_slice(&[viewport]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Spectrum and waveform textures
+        let spectrum_texture = AudioTexture::new(
+            device,
+            (audio::SPECTRUM_SIZE as u32, SPECTRUM_HISTORY),
+            "spectrum_texture",
+        );
+        let waveform_texture =
+            AudioTexture::new(device, (audio::WAVEFORM_SIZE as u32, 1), "waveform_texture");
+
         // Universal bind group
         let universal_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -187,7 +342,7 @@ impl Display {
                         binding: 0,
                         visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
                             has_dynamic_offset: false,
                             min_binding_size: None,
                         },
@@ -213,6 +368,28 @@ impl Display {
                         },
                         count: None,
                     },
+                    Texture::create_audio_bind_group_layout_entry(3),
+                    Texture::create_audio_bind_group_layout_entry(4),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
                 label: Some("universal_bind_group_layout"),
             });
@@ -232,16 +409,29 @@ impl Display {
                     binding: 2,
                     resource: size_buffer.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(spectrum_texture.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(waveform_texture.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: viewport_buffer.as_entire_binding(),
+                },
             ],
             label: Some("universal_bind_group"),
         });
 
         // Textures bind group
-        let framebuffer = Texture::new_framebuffer(
-            &device,
-            (config.width, config.height),
-            "framebuffer texture",
-        );
+        let framebuffer =
+            Texture::new_framebuffer(device, (config.width, config.height), "framebuffer texture");
 
         let texture_bind_group_layout: wgpu::BindGroupLayout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -253,25 +443,25 @@ impl Display {
             });
 
         let framebuffer_texture_bind_group =
-            framebuffer.create_bind_group(&device, &texture_bind_group_layout);
+            framebuffer.create_bind_group(device, &texture_bind_group_layout);
 
         let logo = Texture::new_image(
             include_bytes!("../image/mf_room_logo.png"),
-            &device,
-            &queue,
+            device,
+            queue,
             "logo",
         )?;
 
         let texture_images = vec![logo];
         let texture_image_bind_group_layout =
-            texture::create_texture_image_bind_group_layout(texture_images.len(), &device);
+            texture::create_texture_image_bind_group_layout(texture_images.len(), device);
         let texture_image_bind_group = texture::create_texture_image_bind_group(
             &texture_images,
-            &device,
+            device,
             &texture_image_bind_group_layout,
         );
 
-        let depth_texture = Texture::new_depth(&device, &config, "depth_texture");
+        let depth_texture = Texture::new_depth(device, &config, "depth_texture");
 
         let bind_groups = vec![
             universal_bind_group,
@@ -281,92 +471,141 @@ impl Display {
         ];
 
         let bind_group_layouts = vec![
-            &universal_bind_group_layout,
-            &camera_bind_group_layout,
-            &texture_bind_group_layout,
-            &texture_image_bind_group_layout,
+            universal_bind_group_layout,
+            camera_bind_group_layout,
+            texture_bind_group_layout,
+            texture_image_bind_group_layout,
         ];
 
-        // Create the pipeline group
-        let bind_group_indices_0 = vec![0, 1, 3];
-        let mut pipeline_group_0 =
-            pipeline::PipelineGroup::new_0(&bind_group_layouts, bind_group_indices_0, &device);
-
-        // Create the pipelines in pipeline group 0
-        let vs_0_state = vs_0::State::new(&mut pipeline_group_0, &device, &config, show)?;
-
-        let pipeline_groups = vec![pipeline_group_0];
-
-        // Create postpipeline
-        let bind_group_indices_post = vec![0, 2];
-
-        let pipeline_post = pipeline::PipelinePost::new(
-            &bind_group_layouts,
-            bind_group_indices_post,
-            &device,
+        let (pipeline_groups, pipeline_post, vs_0_state) = create_pipelines(
+            &bind_group_layouts.iter().collect::<Vec<_>>(),
+            device,
             &config,
-            match show {
-                vs_0::Show::Lua => vs_0::POST_SHADER_0,
-                vs_0::Show::MariusJulien => vs_0::POST_SHADER_1,
-            },
+            show.clone(),
+            post.as_deref().unwrap_or(&show),
+            nb_bands,
+            seed,
         )?;
 
+        let overlay = Overlay::new(device, config.format);
+
         Ok(Self {
-            surface,
-            device,
-            queue,
+            gpu,
+            target,
             config,
             size,
-            window,
-            start_time,
             pipeline_groups,
             pipeline_post,
             camera,
             depth_texture,
             framebuffer,
+            spectrum_texture,
+            waveform_texture,
             audio_buffer,
             time_buffer,
             size_buffer,
+            params_buffer,
+            viewport_buffer,
             camera_buffer,
             bind_groups,
-            texture_bind_group_layout,
+            bind_group_layouts,
             audio_data,
             vs_0_state,
+            post,
+            seed,
+            span,
+            blackout: false,
+            shader_errors: BTreeMap::new(),
+            overlay,
         })
     }
 
-    pub fn window(&self) -> &Window {
-        &self.window
+    // Replaces the pipelines by the ones of another visual show
+    pub fn set_show(&mut self, show: Rc<Show>) -> Result<(), DisplayError> {
+        let (pipeline_groups, pipeline_post, vs_0_state) = create_pipelines(
+            &self.bind_group_layouts.iter().collect::<Vec<_>>(),
+            &self.gpu.device,
+            &self.config,
+            show.clone(),
+            self.post.as_deref().unwrap_or(&show),
+            self.audio_data.gain.len(),
+            self.seed,
+        )?;
+        self.pipeline_groups = pipeline_groups;
+        self.pipeline_post = pipeline_post;
+        self.vs_0_state = vs_0_state;
+        Ok(())
     }
 
-    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
+    // Rebuilds the pipelines using the shader file, keeping the previous ones if it is invalid
+    pub fn reload_shader(&mut self, file: &str, source: &str) -> Result<(), DisplayError> {
+        let show = self.vs_0_state.show().clone();
+        let mut result = Ok(());
+        for (i, p) in show.pipelines.iter().enumerate() {
+            if p.shader_file == file {
+                let r = self.pipeline_groups[0].reload_pipeline(i, source, file, &self.gpu.device);
+                result = result.and(r);
+            }
+        }
+        if self.post.as_ref().unwrap_or(&show).post_file == file {
+            let r = self
+                .pipeline_post
+                .reload(source, file, &self.gpu.device, &self.config);
+            result = result.and(r);
+        }
+
+        match &result {
+            Ok(()) => self.shader_errors.remove(file),
+            Err(e) => self.shader_errors.insert(file.to_string(), e.to_string()),
+        };
+        let text = self
+            .shader_errors
+            .iter()
+            .map(|(f, e)| format!("{f}: {e}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.overlay
+            .set_text(&self.gpu.device, &self.gpu.queue, &text, self.size);
+        Ok(result?)
+    }
+
+    // The offscreen displays keep their size
+    pub fn resize(&mut self, new_size: [u32; 2]) {
+        if new_size[0] > 0 && new_size[1] > 0 && matches!(self.target, RenderTarget::Surface(_)) {
             self.size = new_size;
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
+            self.config.width = new_size[0];
+            self.config.height = new_size[1];
 
             // Create new textures with new size
-            self.depth_texture = Texture::new_depth(&self.device, &self.config, "depth_texture");
+            self.depth_texture =
+                Texture::new_depth(&self.gpu.device, &self.config, "depth_texture");
             self.framebuffer = Texture::new_framebuffer(
-                &self.device,
-                (new_size.width, new_size.height),
+                &self.gpu.device,
+                (new_size[0], new_size[1]),
                 "framebuffer texture",
             );
             // Update the bind group of relevant textures
             self.bind_groups[2] = self
                 .framebuffer
-                .create_bind_group(&self.device, &self.texture_bind_group_layout);
-            self.surface.configure(&self.device, &self.config);
-
-            // Update window size
-            let size_data: [u32; 2] = [self.size.width, self.size.height];
-            self.queue
-                .write_buffer(&self.size_buffer, 0, bytemuck::cast_slice(&[size_data]));
-
-            // Update camera
-            self.camera.aspect = self.config.width as f32 / self.config.height as f32;
-            let camera_uniform: CameraUniform = self.camera.into();
-            self.queue.write_buffer(
+                .create_bind_group(&self.gpu.device, &self.bind_group_layouts[2]);
+            self.target.configure(&self.gpu.device, &self.config);
+
+            // Update the canvas size, the viewport, the camera and the overlay
+            self.overlay.resize(&self.gpu.queue, new_size);
+            let span = self.span.unwrap_or_else(|| Span::full(new_size));
+            self.gpu
+                .queue
+                .write_buffer(&self.size_buffer, 0, bytemuck::cast_slice(&[span.canvas]));
+            self.gpu.queue.write_buffer(
+                &self.viewport_buffer,
+                0,
+                bytemuck::cast_slice(&[span.viewport(new_size)]),
+            );
+
+            self.camera.aspect = span.aspect();
+            let (clip_scale, clip_offset) = span.clip_transform();
+            let camera_uniform = CameraUniform::from(self.camera).crop(clip_scale, clip_offset);
+            self.gpu.queue.write_buffer(
                 &self.camera_buffer,
                 0,
                 bytemuck::cast_slice(&[camera_uniform]),
@@ -374,31 +613,51 @@ impl Display {
         }
     }
 
-    pub fn update(&mut self, audio: &Arc<Mutex<audio::Data>>) {
+    pub fn update(&mut self, time: f32, audio_frames: &[audio::Data], controls: &Controls) {
         // println!("{:?}", self.frame_buffer);
         // Update audio
-        let audio_data = *audio.lock().unwrap();
-        self.queue
-            .write_buffer(&self.audio_buffer, 0, bytemuck::cast_slice(&[audio_data]));
+        for audio_data in audio_frames {
+            self.spectrum_texture.write_row(
+                &self.gpu.queue,
+                audio_data.frame % SPECTRUM_HISTORY,
+                &audio_data.spectrum,
+            );
+        }
+        if let Some(audio_data) = audio_frames.last() {
+            self.gpu
+                .queue
+                .write_buffer(&self.audio_buffer, 0, &audio_data.to_bytes());
+            self.waveform_texture
+                .write_row(&self.gpu.queue, 0, &audio_data.waveform);
+        }
 
         // Update time
-        let time = self.start_time.elapsed().as_secs_f32();
-        self.queue
+        self.gpu
+            .queue
             .write_buffer(&self.time_buffer, 0, bytemuck::cast_slice(&[time]));
 
+        // Update controls
+        self.blackout = controls.blackout;
+        self.gpu.queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[controls.params]),
+        );
+
         // Update the InstanceModels
         self.vs_0_state.update(
             &mut self.pipeline_groups[0].pipelines,
             time,
             &self.audio_data,
-            &audio_data,
+            audio_frames,
+            controls,
         );
 
         for p_g in &self.pipeline_groups {
             for p in &p_g.pipelines {
                 for i_m in &p.instance_models {
                     let instance_data = i_m.instances.iter().map(Instance::raw).collect::<Vec<_>>();
-                    self.queue.write_buffer(
+                    self.gpu.queue.write_buffer(
                         &i_m.instance_buffer,
                         0,
                         bytemuck::cast_slice(&instance_data),
@@ -407,16 +666,37 @@ impl Display {
             }
         }
 
-        self.audio_data = audio_data;
+        if let Some(audio_data) = audio_frames.last() {
+            self.audio_data = audio_data.clone();
+        }
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        match &self.target {
+            RenderTarget::Surface(surface) => {
+                let output = surface.get_current_texture()?;
+                let view = output
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                self.draw(&view);
+                output.present();
+            }
+            RenderTarget::Offscreen(o) => self.draw(&o.view()),
+        }
+        Ok(())
+    }
 
+    // Reads back the last frame rendered offscreen, as RGBA rows
+    pub fn read_frame(&self) -> Result<Vec<u8>, DisplayError> {
+        match &self.target {
+            RenderTarget::Offscreen(o) => Ok(o.read(&self.gpu.device, &self.gpu.queue)?),
+            RenderTarget::Surface(_) => Err(DisplayError::WindowReadback),
+        }
+    }
+
+    fn draw(&self, view: &wgpu::TextureView) {
         let mut encoder = self
+            .gpu
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
@@ -464,7 +744,7 @@ impl Display {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Post Processing"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         //load: wgpu::LoadOp::Load,
@@ -480,16 +760,17 @@ impl Display {
                 depth_stencil_attachment: None,
             });
 
-            let bg_indices = self.pipeline_post.get_bind_group_indices();
-            for (u, i) in bg_indices.iter().enumerate() {
-                render_pass.set_bind_group(u as u32, &self.bind_groups[*i], &[]);
+            // The output is only cleared during a blackout
+            if !self.blackout {
+                let bg_indices = self.pipeline_post.get_bind_group_indices();
+                for (u, i) in bg_indices.iter().enumerate() {
+                    render_pass.set_bind_group(u as u32, &self.bind_groups[*i], &[]);
+                }
+                self.pipeline_post.draw(&mut render_pass);
             }
-            self.pipeline_post.draw(&mut render_pass);
+            self.overlay.draw(&mut render_pass);
         }
 
-        self.queue.submit(iter::once(encoder.finish()));
-        output.present();
-
-        Ok(())
+        self.gpu.queue.submit(iter::once(encoder.finish()));
     }
 }
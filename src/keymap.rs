@@ -0,0 +1,125 @@
+use crate::control::{Controls, Param, Trigger};
+use serde::Deserialize;
+use std::collections::HashMap;
+use winit::event::VirtualKeyCode;
+
+const INTENSITY_STEP: f32 = 0.05;
+const SENSITIVITY_STEP: f32 = 0.1;
+// The activation thresholds are lowered by at most MAX_SENSITIVITY
+const MAX_SENSITIVITY: f32 = 1.0;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Quit,
+    // Reset the statistics of the audio bands
+    ResetStats,
+    // Force a dynamic pipeline into the selected band
+    #[serde(rename = "force-noise-3d")]
+    ForceNoise3d,
+    ForceFull,
+    ForceDisk,
+    #[serde(rename = "force-wf-3d")]
+    ForceWf3d,
+    // Select the next band
+    NextBand,
+    // Activate the pipeline of the selected band
+    Activate,
+    Fullscreen,
+    Blackout,
+    // Only display the background and the logo
+    LogoOnly,
+    NextPalette,
+    IntensityUp,
+    IntensityDown,
+    // Lower or raise the activation thresholds of the gains
+    SensitivityUp,
+    SensitivityDown,
+}
+
+const DEFAULT_BINDINGS: &[(Action, &[VirtualKeyCode])] = {
+    use Action::*;
+    use VirtualKeyCode as K;
+    &[
+        (Quit, &[K::Escape]),
+        (ResetStats, &[K::R]),
+        (ForceNoise3d, &[K::Key1, K::Numpad1]),
+        (ForceFull, &[K::Key2, K::Numpad2]),
+        (ForceDisk, &[K::Key3, K::Numpad3]),
+        (ForceWf3d, &[K::Key4, K::Numpad4]),
+        (NextBand, &[K::Tab]),
+        (Activate, &[K::Space]),
+        (Fullscreen, &[K::F]),
+        (Blackout, &[K::B]),
+        (LogoOnly, &[K::L]),
+        (NextPalette, &[K::P]),
+        (IntensityUp, &[K::Plus, K::Equals, K::NumpadAdd]),
+        (IntensityDown, &[K::Minus, K::NumpadSubtract]),
+        (SensitivityUp, &[K::RBracket]),
+        (SensitivityDown, &[K::LBracket]),
+    ]
+};
+
+pub struct Keymap {
+    actions: HashMap<VirtualKeyCode, Action>,
+}
+
+impl Keymap {
+    // The bindings replace the default keys of their actions
+    pub fn new(bindings: &HashMap<Action, Vec<VirtualKeyCode>>) -> Self {
+        let mut actions = HashMap::new();
+        for (action, keys) in DEFAULT_BINDINGS {
+            if !bindings.contains_key(action) {
+                actions.extend(keys.iter().map(|k| (*k, *action)));
+            }
+        }
+        for (action, keys) in bindings {
+            actions.extend(keys.iter().map(|k| (*k, *action)));
+        }
+        Keymap { actions }
+    }
+
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.actions.get(&key).copied()
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::new(&HashMap::new())
+    }
+}
+
+// Applies the actions acting on the controls
+pub fn perform(action: Action, controls: &mut Controls) {
+    match action {
+        Action::ForceNoise3d => controls.force = Some(Trigger::Noise3d),
+        Action::ForceFull => controls.force = Some(Trigger::Full),
+        Action::ForceDisk => controls.force = Some(Trigger::Disk),
+        Action::ForceWf3d => controls.force = Some(Trigger::Wf3d),
+        Action::NextBand => controls.band = controls.band.wrapping_add(1),
+        Action::Activate => controls.activate = true,
+        Action::Blackout => controls.blackout = !controls.blackout,
+        Action::LogoOnly => controls.logo_only = !controls.logo_only,
+        Action::NextPalette => controls.next_palette = true,
+        Action::IntensityUp | Action::IntensityDown => {
+            let step = match action {
+                Action::IntensityUp => INTENSITY_STEP,
+                _ => -INTENSITY_STEP,
+            };
+            let intensity = controls.params.get(Param::Intensity) + step;
+            controls.params.set(Param::Intensity, intensity);
+            log::info!("Intensity: {:.2}", controls.params.get(Param::Intensity));
+        }
+        Action::SensitivityUp | Action::SensitivityDown => {
+            let step = match action {
+                Action::SensitivityUp => SENSITIVITY_STEP,
+                _ => -SENSITIVITY_STEP,
+            };
+            controls.sensitivity =
+                (controls.sensitivity + step).clamp(-MAX_SENSITIVITY, MAX_SENSITIVITY);
+            log::info!("Sensitivity: {:+.1}", controls.sensitivity);
+        }
+        Action::Quit | Action::ResetStats | Action::Fullscreen => {}
+    }
+}
@@ -1,36 +1,184 @@
 mod audio;
 mod camera;
+mod canvas;
 mod color;
+mod config;
+mod control;
 mod display;
 mod instance;
+mod keymap;
+mod midi;
 mod model;
+mod offline;
+mod osc;
+mod overlay;
 mod pipeline;
+mod shader;
+mod show;
+mod target;
 mod texture;
 mod vs_0;
-use display::Display;
+mod watcher;
+mod window;
+use display::{Display, Gpu};
+use std::collections::{BTreeSet, HashSet};
+use std::rc::Rc;
+use std::time::Instant;
+use target::RenderTarget;
 
-pub use vs_0::Show;
+pub use audio::clock::ClockSource;
+pub use audio::meter::{LogMeter, Meter, MeterKind, ProbeMeter, SilentMeter, TerminalMeter};
+pub use audio::{calibrate, list_devices, AudioConfig, ChannelMode, WindowFunction};
+pub use config::{
+    BandConfig, CameraConfig, CanvasConfig, CcMapping, Config, ConfigError, DisplayConfig,
+    EnvelopeConfig, FreqRange, MidiMapping, NoiseProfile, NoteMapping, RegionConfig,
+};
+pub use control::{Param, Trigger};
+pub use display::OutputConfig;
+pub use keymap::{Action, Keymap};
+pub use midi::{learn, list_midi_ports, MidiConfig};
+pub use offline::{render, RenderConfig};
+pub use osc::OscConfig;
+pub use shader::check_shaders;
+pub use show::{Show, ShowError, ShowLibrary};
+pub use window::{list_monitors, WindowConfig, WindowSize};
 
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::Fullscreen,
 };
 
-pub async fn run(nb_displays: u32, show: vs_0::Show) {
+pub async fn run(
+    mut output_config: OutputConfig,
+    audio_config: AudioConfig,
+    midi_config: Option<MidiConfig>,
+    osc_config: Option<OscConfig>,
+    clock_source: ClockSource,
+    keymap: Keymap,
+) {
     let event_loop = EventLoop::new();
 
+    // Init the external clock
+    let (clock_sender, clock_receiver) = match clock_source {
+        ClockSource::Audio => (None, None),
+        _ => {
+            let (s, r) = audio::clock::channel();
+            (Some(s), Some(r))
+        }
+    };
+
     // Init audio
-    //TODO: remove unwrap
-    let (audio_data, _stream) = audio::init(2048, 20, 20000).unwrap();
+    let (mut audio_receiver, _stream) =
+        match audio::init(&audio_config, audio_config.meter.meter(), clock_receiver) {
+            Ok(a) => a,
+            Err(e) => {
+                eprintln!("[ERROR] {e}");
+                std::process::exit(1);
+            }
+        };
+
+    // Init MIDI
+    let (midi_clock, link_clock) = match clock_source {
+        ClockSource::Midi => (clock_sender, None),
+        _ => (None, clock_sender),
+    };
+    let mut midi = match midi_config
+        .map(|c| midi::Midi::connect(c, midi_clock))
+        .transpose()
+    {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+    };
+
+    // Init Ableton Link
+    if let Some(c) = link_clock {
+        if let Err(e) = audio::link::spawn(c) {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+    }
+
+    // Init OSC
+    let mut osc = match osc_config.as_ref().map(osc::Osc::bind).transpose() {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+    };
+
+    // Watch the shaders to reload them when they are modified
+    let watcher = match output_config
+        .shows
+        .shader_dir()
+        .map(watcher::ShaderWatcher::new)
+        .transpose()
+    {
+        Ok(w) => w,
+        Err(e) => {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+    };
+
+    // Regions of the canvas rendered by the displays
+    let regions: Vec<_> = output_config.displays.iter().map(|d| d.region).collect();
+    let spans = match canvas::spans(output_config.canvas.as_ref(), &regions) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+    };
 
     // Initialize the displays
+    let mut windows = vec![];
+    for c in &output_config.displays {
+        match window::build_window(&event_loop, &c.window) {
+            Ok(w) => windows.push(w),
+            Err(e) => {
+                eprintln!("[ERROR] {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+    let (gpu, surfaces) = match Gpu::new(&windows, output_config.force_fallback_adapter).await {
+        Ok(g) => g,
+        Err(e) => {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+    };
+    let gpu = Rc::new(gpu);
+
+    let nb_bands = audio_receiver.latest().gain.len();
+    // The displays of a canvas render the same scene
+    let sync = output_config.sync || output_config.canvas.is_some();
+    let seed = sync.then(rand::random);
+    // Each display is dropped before its window, its surface being created from the window
     let mut displays = vec![];
-    for _ in 0..nb_displays {
-        let window = WindowBuilder::new().build(&event_loop).unwrap();
-        let display: Result<Display, display::DisplayError> = Display::new(window, show).await;
-        match display {
-            Ok(d) => displays.push(d),
+    for (((window, surface), c), span) in windows
+        .into_iter()
+        .zip(surfaces)
+        .zip(&output_config.displays)
+        .zip(spans)
+    {
+        let size = window.inner_size();
+        match Display::new(
+            gpu.clone(),
+            RenderTarget::Surface(surface),
+            [size.width, size.height],
+            c,
+            &output_config.shows,
+            nb_bands,
+            seed,
+            span,
+        ) {
+            Ok(d) => displays.push((d, window)),
             Err(e) => {
                 eprintln!("[ERROR] {e}");
                 std::process::exit(1);
@@ -38,40 +186,61 @@ pub async fn run(nb_displays: u32, show: vs_0::Show) {
         }
     }
 
+    let mut controls = control::Controls::default();
+    let mut held_keys = HashSet::new();
+    let start_time = Instant::now();
+
     event_loop.run(move |event, _, control_flow| {
         match event {
             Event::WindowEvent {
                 ref event,
                 window_id,
             } => {
-                for s in &mut displays {
-                    if window_id == s.window().id() {
+                for (s, window) in &mut displays {
+                    if window.id() == window_id {
                         match event {
                             WindowEvent::KeyboardInput {
                                 input:
                                     KeyboardInput {
-                                        state: ElementState::Pressed,
-                                        virtual_keycode: Some(VirtualKeyCode::R),
-                                        ..
-                                    },
-                                ..
-                            } => {}
-                            WindowEvent::CloseRequested
-                            | WindowEvent::KeyboardInput {
-                                input:
-                                    KeyboardInput {
-                                        state: ElementState::Pressed,
-                                        virtual_keycode: Some(VirtualKeyCode::Escape),
+                                        state,
+                                        virtual_keycode: Some(key),
                                         ..
                                     },
                                 ..
-                            } => *control_flow = ControlFlow::Exit,
+                            } => {
+                                // Only the first press of a held key is applied
+                                let ignored = match state {
+                                    ElementState::Pressed => !held_keys.insert(*key),
+                                    ElementState::Released => {
+                                        held_keys.remove(key);
+                                        true
+                                    }
+                                };
+                                match keymap.action(*key) {
+                                    _ if ignored => {}
+                                    Some(Action::Quit) => *control_flow = ControlFlow::Exit,
+                                    Some(Action::ResetStats) => {
+                                        audio_receiver.send(audio::Command::Reset)
+                                    }
+                                    Some(Action::Fullscreen) => {
+                                        window.set_fullscreen(match window.fullscreen() {
+                                            Some(_) => None,
+                                            None => Some(Fullscreen::Borderless(None)),
+                                        });
+                                    }
+                                    Some(a) => keymap::perform(a, &mut controls),
+                                    None => {}
+                                }
+                            }
+                            // The keys released while the window is unfocused are never seen
+                            WindowEvent::Focused(false) => held_keys.clear(),
+                            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
                             WindowEvent::Resized(physical_size) => {
-                                s.resize(*physical_size);
+                                s.resize([physical_size.width, physical_size.height]);
                             }
                             WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
                                 // new_inner_size is &mut so w have to dereference it twice
-                                s.resize(**new_inner_size);
+                                s.resize([new_inner_size.width, new_inner_size.height]);
                             }
                             _ => {}
                         }
@@ -81,9 +250,8 @@ pub async fn run(nb_displays: u32, show: vs_0::Show) {
             }
 
             Event::RedrawRequested(window_id) => {
-                for d in &mut displays {
-                    if window_id == d.window().id() {
-                        d.update(&audio_data);
+                for (d, window) in &mut displays {
+                    if window.id() == window_id {
                         match d.render() {
                             Ok(_) => {}
                             // Reconfigure the surface if it's lost or outdated
@@ -103,11 +271,56 @@ pub async fn run(nb_displays: u32, show: vs_0::Show) {
             }
 
             Event::MainEventsCleared => {
-                // RedrawRequested will only trigger once, unless we manually
-                // request it.
-                for s in &mut displays {
-                    s.window().request_redraw();
+                // Frames analysed since the previous update, shared by all the displays
+                let audio_frames = audio_receiver.new_frames();
+                if let Some(m) = &mut midi {
+                    m.apply(&mut controls);
+                }
+                if let Some(o) = &mut osc {
+                    o.apply(&mut controls);
+                    o.broadcast(&audio_frames);
+                }
+
+                for (file, source) in watcher.iter().flat_map(|w| w.changes()) {
+                    output_config.shows.update_shader(&file, &source);
+                    // The displays showing the same show fail with the same errors
+                    let mut errors = BTreeSet::new();
+                    for (d, _) in &mut displays {
+                        if let Err(e) = d.reload_shader(&file, &source) {
+                            errors.insert(e.to_string());
+                        }
+                    }
+                    if errors.is_empty() {
+                        log::info!("Reloaded the shader {file}");
+                    }
+                    for e in errors {
+                        log::error!("Failed to reload the shader {file}: {e}");
+                    }
+                }
+
+                if let Some(name) = &controls.show {
+                    match output_config.shows.get(name) {
+                        Ok(show) => {
+                            for (d, _) in &mut displays {
+                                if let Err(e) = d.set_show(show.clone()) {
+                                    log::error!("Failed to switch to the show {name}: {e}");
+                                }
+                            }
+                        }
+                        Err(e) => log::error!("{e}"),
+                    }
+                }
+
+                // The displays are updated together, so that the synchronized displays stay in sync
+                // even when a window is redrawn more often
+                let time = start_time.elapsed().as_secs_f32();
+                for (d, window) in &mut displays {
+                    d.update(time, &audio_frames, &controls);
+                    // RedrawRequested will only trigger once, unless we manually
+                    // request it.
+                    window.request_redraw();
                 }
+                controls.clear();
             }
             _ => (),
         }
@@ -1,15 +1,450 @@
 use clap::Parser;
-use gseq::{run, Show};
+use gseq::{
+    calibrate, check_shaders, learn, list_devices, list_midi_ports, list_monitors, render, run,
+    AudioConfig, ChannelMode, ClockSource, Config, DisplayConfig, Keymap, MeterKind, MidiConfig,
+    MidiMapping, NoiseProfile, OscConfig, OutputConfig, RenderConfig, ShowLibrary, WindowConfig,
+    WindowFunction, WindowSize,
+};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Displayed visual show
-    #[arg(short, long, default_value_t = Show::MariusJulien)]
-    show: Show,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Displayed visual show, either a built-in show (marius-julien or lua) or a show of the
+    /// show directory
+    #[arg(short, long, default_value = "marius-julien")]
+    show: String,
+
+    /// Directory of show files (TOML), the shows with the same names as the built-in ones
+    /// replacing them
+    #[arg(long)]
+    show_dir: Option<PathBuf>,
+
+    /// Directory of WGSL shaders replacing the shaders of the shows with the same file names,
+    /// reloaded when they are modified
+    #[arg(long)]
+    shader_dir: Option<PathBuf>,
+
+    /// Audio input device, either its id or a part of its name
+    #[arg(long)]
+    audio_device: Option<String>,
+
+    /// Audio host (e.g. alsa, jack)
+    #[arg(long)]
+    audio_host: Option<String>,
+
+    /// Audio file (WAV or FLAC) analysed instead of the input device
+    #[arg(long)]
+    audio_file: Option<PathBuf>,
+
+    /// Playback speed of the audio file, 0 to analyse it as fast as possible
+    #[arg(long, default_value_t = 1.0, value_parser = parse_speed)]
+    audio_speed: f32,
+
+    /// Play the audio file on the default output device
+    #[arg(long, requires = "audio_file")]
+    playback: bool,
+
+    /// Analysed audio channels: a channel id, mix (mean of all the channels) or pairs (each
+    /// pair of channels has its own bands)
+    #[arg(long, default_value = "0")]
+    audio_channels: ChannelMode,
+
+    /// Configuration file (TOML)
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Number of audio frequency bands, ignored when the bands are declared in the configuration
+    /// file
+    #[arg(short, long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..=32))]
+    bands: u32,
+
+    /// Number of samples of each analysed audio frame
+    #[arg(long, default_value_t = 2048, value_parser = clap::value_parser!(u32).range(64..=65536))]
+    fft_size: u32,
+
+    /// Number of samples between two analysed audio frames, smaller than the FFT size to overlap
+    /// the frames and lower the latency [default: FFT size]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    hop_size: Option<u32>,
+
+    /// Window function applied to the analysed audio frames
+    #[arg(long, value_enum, default_value_t = WindowFunction::Hann)]
+    window: WindowFunction,
+
+    /// Lowest analysed frequency, in Hz
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u32).range(1..))]
+    min_freq: u32,
+
+    /// Highest analysed frequency, in Hz
+    #[arg(long, default_value_t = 20000)]
+    max_freq: u32,
+
+    /// Consumer of the audio analysis: terminal (gains printed in the terminal), log (gains
+    /// sent to the logs) or none
+    #[arg(long, value_enum, default_value_t = MeterKind::Terminal)]
+    meter: MeterKind,
+
+    /// Duration of the statistics window of the audio bands, in seconds (reset with the R key)
+    #[arg(long, default_value_t = 5.0)]
+    stat_window: f32,
+
+    /// Noise profile holding the noise floor of each audio band, used to detect silence
+    #[arg(long)]
+    noise_profile: Option<PathBuf>,
+
+    /// Measure the noise floor of the room during the given number of seconds, save it to the
+    /// noise profile and exit
+    #[arg(long, value_name = "SECONDS", requires = "noise_profile")]
+    calibrate: Option<f32>,
+
+    /// List the available audio input devices and exit
+    #[arg(long)]
+    list_devices: bool,
+
+    /// MIDI input port, either its id or a part of its name
+    #[arg(long)]
+    midi: Option<String>,
+
+    /// Mapping of the MIDI notes to the triggered pipelines and of the MIDI controllers to the
+    /// parameters (TOML)
+    #[arg(long)]
+    midi_mapping: Option<PathBuf>,
+
+    /// Learn the MIDI mapping from the played notes and moved controllers, save it to the MIDI
+    /// mapping file and exit
+    #[arg(long, requires = "midi", requires = "midi_mapping")]
+    midi_learn: bool,
+
+    /// List the available MIDI input ports and exit
+    #[arg(long)]
+    list_midi_ports: bool,
+
+    /// Source of the tempo and of the beats: audio (beat tracking of the audio input), midi
+    /// (MIDI clock of the MIDI input port) or link (tempo of the Ableton Link session)
+    #[arg(long, value_enum, default_value_t = ClockSource::Audio, requires_if("midi", "midi"))]
+    clock: ClockSource,
+
+    /// Address on which OSC messages are received (e.g. 0.0.0.0:9000)
+    #[arg(long)]
+    osc: Option<SocketAddr>,
+
+    /// Address to which the audio gains are sent as OSC messages
+    #[arg(long, requires = "osc")]
+    osc_broadcast: Option<SocketAddr>,
+
+    /// Monitor of the window, either its id or a part of its name [default: primary monitor]
+    #[arg(long)]
+    monitor: Option<String>,
+
+    /// Display the window in fullscreen on its monitor
+    #[arg(long)]
+    fullscreen: bool,
+
+    /// Inner size of the window, in pixels (e.g. 1920x1080)
+    #[arg(long, value_name = "WIDTHxHEIGHT", conflicts_with = "fullscreen")]
+    size: Option<WindowSize>,
+
+    /// Remove the title bar and the borders of the window
+    #[arg(long)]
+    borderless: bool,
+
+    /// Hide the mouse cursor over the window
+    #[arg(long)]
+    hide_cursor: bool,
+
+    /// List the available monitors and exit
+    #[arg(long)]
+    list_monitors: bool,
+
+    /// Use a software adapter, for the machines without GPU (e.g. CI)
+    #[arg(long)]
+    force_fallback_adapter: bool,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Validate the WGSL shaders of a directory against the pipelines using them in the built-in
+    /// shows and in the show files of the directory, and exit
+    CheckShaders {
+        /// Directory of the shaders
+        dir: PathBuf,
+    },
+    /// Render the show offline to PNG frames, driven by an audio file, and exit
+    Render {
+        /// Audio file (WAV or FLAC) analysed in lockstep with the frames
+        #[arg(long)]
+        audio: PathBuf,
+
+        /// Directory of the PNG frames
+        #[arg(long)]
+        out: PathBuf,
+
+        /// Frames per second
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+        fps: u32,
+
+        /// Size of the frames, in pixels
+        #[arg(long, value_name = "WIDTHxHEIGHT", default_value = "1920x1080")]
+        size: WindowSize,
+
+        /// Video file also encoded from the frames and the audio file, with ffmpeg
+        #[arg(long)]
+        video: Option<PathBuf>,
+
+        /// Rendered duration, in seconds [default: duration of the audio file]
+        #[arg(long)]
+        duration: Option<f32>,
+
+        /// Seed of the random choices, to render the same frames again
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+}
+
+fn parse_speed(s: &str) -> Result<f32, String> {
+    let speed: f32 = s.parse().map_err(|e| format!("{e}"))?;
+    if !(speed.is_finite() && speed >= 0.0) {
+        return Err("expected a positive speed, or 0".to_string());
+    }
+    Ok(speed)
 }
 
 fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn,gseq=info"))
+        .init();
     let args = Args::parse();
-    pollster::block_on(run(1, args.show));
+
+    if let Some(Command::CheckShaders { dir }) = &args.command {
+        if let Err(e) = check_shaders(dir) {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.list_devices {
+        if let Err(e) = list_devices(args.audio_host.as_deref()) {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.list_monitors {
+        if let Err(e) = list_monitors() {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.list_midi_ports {
+        if let Err(e) = list_midi_ports() {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let (true, Some(port), Some(path)) = (args.midi_learn, &args.midi, &args.midi_mapping) {
+        let mapping = match learn(port) {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("[ERROR] {e}");
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = mapping.save(path) {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let midi_mapping = match &args.midi_mapping {
+        Some(path) => match MidiMapping::load(path) {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("[ERROR] {e}");
+                std::process::exit(1);
+            }
+        },
+        None => MidiMapping::default(),
+    };
+    let midi_config = args.midi.map(|port| MidiConfig {
+        port,
+        mapping: midi_mapping,
+    });
+
+    let config = match &args.config {
+        Some(path) => match Config::load(path) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("[ERROR] {e}");
+                std::process::exit(1);
+            }
+        },
+        None => Config::default(),
+    };
+
+    let noise_floors = match (&args.noise_profile, args.calibrate) {
+        (Some(path), None) => match NoiseProfile::load(path) {
+            Ok(p) => Some(p.floors),
+            Err(e) => {
+                eprintln!("[ERROR] {e}");
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+
+    let keymap = Keymap::new(&config.keys);
+
+    let mut shows = match ShowLibrary::new(args.shader_dir.clone()) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+    };
+    if let Some(dir) = &args.show_dir {
+        if let Err(e) = shows.load_dir(dir) {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+    }
+
+    let audio_config = AudioConfig {
+        host: args.audio_host,
+        device: args.audio_device,
+        file: args.audio_file,
+        speed: args.audio_speed,
+        playback: args.playback,
+        channel_mode: args.audio_channels,
+        nb_bands: args.bands as usize,
+        bands: config.bands,
+        envelope: config.envelope,
+        fft_size: args.fft_size as usize,
+        hop_size: args.hop_size.unwrap_or(args.fft_size) as usize,
+        window: args.window,
+        min_freq: args.min_freq,
+        max_freq: args.max_freq,
+        stat_window: args.stat_window,
+        noise_floors,
+        meter: args.meter,
+    };
+
+    if let (Some(duration), Some(path)) = (args.calibrate, &args.noise_profile) {
+        println!(
+            "[CALIBRATION] Measuring the noise floor during {duration} s, keep the room quiet"
+        );
+        let profile = match calibrate(&audio_config, duration) {
+            Ok(floors) => NoiseProfile { floors },
+            Err(e) => {
+                eprintln!("[ERROR] {e}");
+                std::process::exit(1);
+            }
+        };
+        for (i, f) in profile.floors.iter().enumerate() {
+            println!("[NOISE FLOOR] audio_channel[{i}]: {f:.1} dB");
+        }
+        if let Err(e) = profile.save(path) {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let osc_config = args.osc.map(|listen| OscConfig {
+        listen,
+        broadcast: args.osc_broadcast,
+    });
+
+    // The window options only apply when no display is declared in the configuration file
+    let window_options = args.monitor.is_some()
+        || args.fullscreen
+        || args.size.is_some()
+        || args.borderless
+        || args.hide_cursor;
+    if window_options && !config.displays.is_empty() {
+        eprintln!(
+            "[ERROR] The window options (--monitor, --fullscreen, --size, --borderless, \
+             --hide-cursor) cannot be used with the displays of the configuration file, set them \
+             in the [display.window] tables"
+        );
+        std::process::exit(1);
+    }
+    let displays = if config.displays.is_empty() {
+        vec![DisplayConfig {
+            show: Some(args.show),
+            window: WindowConfig {
+                monitor: args.monitor,
+                fullscreen: args.fullscreen,
+                size: args.size,
+                borderless: args.borderless,
+                hide_cursor: args.hide_cursor,
+            },
+            ..Default::default()
+        }]
+    } else {
+        config
+            .displays
+            .into_iter()
+            .map(|d| DisplayConfig {
+                show: d.show.or_else(|| Some(args.show.clone())),
+                ..d
+            })
+            .collect()
+    };
+
+    let output_config = OutputConfig {
+        displays,
+        canvas: config.canvas,
+        sync: config.sync,
+        shows,
+        force_fallback_adapter: args.force_fallback_adapter,
+    };
+
+    if let Some(Command::Render {
+        audio,
+        out,
+        fps,
+        size,
+        video,
+        duration,
+        seed,
+    }) = args.command
+    {
+        let audio_config = AudioConfig {
+            file: Some(audio),
+            ..audio_config
+        };
+        let render_config = RenderConfig {
+            out,
+            fps,
+            size,
+            video,
+            duration,
+            seed,
+        };
+        if let Err(e) = pollster::block_on(render(output_config, audio_config, render_config)) {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    pollster::block_on(run(
+        output_config,
+        audio_config,
+        midi_config,
+        osc_config,
+        args.clock,
+        keymap,
+    ));
 }
@@ -0,0 +1,403 @@
+use crate::audio::clock::{ClockSender, ClockState};
+use crate::config::{CcMapping, MidiMapping, NoteMapping};
+use crate::control::{Controls, Param, Trigger};
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiInputPort};
+use rtrb::{Consumer, RingBuffer};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CLIENT_NAME: &str = "gseq";
+// Number of MIDI messages buffered between two frames
+const MESSAGE_CAPACITY: usize = 256;
+// Duration after which a learnt control is skipped
+const LEARN_TIMEOUT: Duration = Duration::from_secs(10);
+const LEARN_POLL_PERIOD: Duration = Duration::from_millis(10);
+const PULSES_PER_BEAT: usize = 24;
+
+// MIDI input port and mapping of its messages
+pub struct MidiConfig {
+    // Id or part of the name of the port
+    pub port: String,
+    pub mapping: MidiMapping,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Message {
+    // Channels from 0 to 15
+    NoteOn { channel: u8, note: u8 },
+    ControlChange { channel: u8, cc: u8, value: u8 },
+}
+
+impl Message {
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        match *bytes {
+            // A note-on with a null velocity is a note-off
+            [status, note, velocity] if status & 0xf0 == 0x90 && velocity > 0 => {
+                Some(Message::NoteOn {
+                    channel: status & 0x0f,
+                    note,
+                })
+            }
+            [status, cc, value] if status & 0xf0 == 0xb0 => Some(Message::ControlChange {
+                channel: status & 0x0f,
+                cc,
+                value,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// Follower of the MIDI clock
+struct MidiClock {
+    sender: ClockSender,
+    // The beats are only known after a start or continue message
+    running: bool,
+    pulses: u64,
+    // Timestamps of the last pulses, in microseconds
+    stamps: VecDeque<u64>,
+}
+
+impl MidiClock {
+    fn new(sender: ClockSender) -> Self {
+        MidiClock {
+            sender,
+            running: false,
+            pulses: 0,
+            stamps: VecDeque::new(),
+        }
+    }
+
+    fn process(&mut self, stamp: u64, bytes: &[u8]) {
+        match bytes {
+            // Start, the next pulse is the first beat
+            [0xfa] => {
+                self.running = true;
+                self.pulses = 0;
+                self.stamps.clear();
+            }
+            // Continue
+            [0xfb] => self.running = true,
+            // Stop, the clock is followed again with the next pulses, tempo only. The tempo is
+            // not measured over the pause.
+            [0xfc] => {
+                self.running = false;
+                self.stamps.clear();
+                self.sender.send(None);
+            }
+            // Pulse
+            [0xf8] => self.pulse(stamp),
+            _ => {}
+        }
+    }
+
+    fn pulse(&mut self, stamp: u64) {
+        self.stamps.push_back(stamp);
+        if self.stamps.len() > PULSES_PER_BEAT + 1 {
+            self.stamps.pop_front();
+        }
+
+        let elapsed = stamp.saturating_sub(self.stamps[0]);
+        if elapsed > 0 {
+            let beats = (self.stamps.len() - 1) as f32 / PULSES_PER_BEAT as f32;
+            self.sender.send(Some(ClockState {
+                bpm: 60e6 * beats / elapsed as f32,
+                beat: self
+                    .running
+                    .then(|| self.pulses as f64 / PULSES_PER_BEAT as f64),
+                // Until the next pulse
+                horizon: 1.0 / PULSES_PER_BEAT as f64,
+                updated: Instant::now(),
+            }));
+        }
+
+        if self.running {
+            self.pulses += 1;
+        }
+    }
+}
+
+fn matches_channel(mapping: Option<u8>, channel: u8) -> bool {
+    mapping.is_none_or(|c| c == channel + 1)
+}
+
+fn select_port(input: &MidiInput, selector: &str) -> Result<MidiInputPort, Box<dyn Error>> {
+    let ports = input.ports();
+    if let Ok(id) = selector.parse::<usize>() {
+        return ports
+            .into_iter()
+            .nth(id)
+            .ok_or_else(|| format!("No MIDI port with id {}", id).into());
+    }
+
+    let pattern = selector.to_lowercase();
+    for p in ports {
+        if input.port_name(&p)?.to_lowercase().contains(&pattern) {
+            return Ok(p);
+        }
+    }
+    Err(format!("No MIDI port matching \"{}\"", selector).into())
+}
+
+pub fn list_midi_ports() -> Result<(), Box<dyn Error>> {
+    let input = MidiInput::new(CLIENT_NAME)?;
+    for (i, p) in input.ports().iter().enumerate() {
+        println!("[MIDI PORT {}] {}", i, input.port_name(p)?);
+    }
+    Ok(())
+}
+
+pub struct Midi {
+    // The port is closed when the connection is dropped
+    _connection: MidiInputConnection<()>,
+    messages: Consumer<Message>,
+    mapping: MidiMapping,
+}
+
+impl Midi {
+    // The MIDI clock is sent to clock
+    pub fn connect(config: MidiConfig, clock: Option<ClockSender>) -> Result<Self, Box<dyn Error>> {
+        let mut input = MidiInput::new(CLIENT_NAME)?;
+        input.ignore(Ignore::SysexAndActiveSense);
+        let port = select_port(&input, &config.port)?;
+        let name = input.port_name(&port)?;
+
+        let (mut producer, messages) = RingBuffer::new(MESSAGE_CAPACITY);
+        let mut clock = clock.map(MidiClock::new);
+        let connection = input.connect(
+            &port,
+            "gseq-input",
+            move |stamp, bytes, _| {
+                if let Some(c) = &mut clock {
+                    c.process(stamp, bytes);
+                }
+                if let Some(m) = Message::parse(bytes) {
+                    if producer.push(m).is_err() {
+                        log::warn!("MIDI message dropped");
+                    }
+                }
+            },
+            (),
+        )?;
+        log::info!("Listening to the MIDI port {}", name);
+
+        Ok(Midi {
+            _connection: connection,
+            messages,
+            mapping: config.mapping,
+        })
+    }
+
+    // Applies the messages received since the previous call
+    pub fn apply(&mut self, controls: &mut Controls) {
+        while let Ok(m) = self.messages.pop() {
+            match m {
+                Message::NoteOn { channel, note } => {
+                    for n in &self.mapping.notes {
+                        if n.note == note && matches_channel(n.channel, channel) {
+                            controls.triggers.push(n.trigger);
+                        }
+                    }
+                }
+                Message::ControlChange { channel, cc, value } => {
+                    for c in &self.mapping.ccs {
+                        if c.cc == cc && matches_channel(c.channel, channel) {
+                            controls.params.set(c.param, value as f32 / 127.0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Waits for a message accepted by filter, None after LEARN_TIMEOUT
+    fn wait(&mut self, filter: impl Fn(&Message) -> bool) -> Option<Message> {
+        // Ignore the messages sent before the prompt
+        while self.messages.pop().is_ok() {}
+
+        let start = Instant::now();
+        while start.elapsed() < LEARN_TIMEOUT {
+            while let Ok(m) = self.messages.pop() {
+                if filter(&m) {
+                    return Some(m);
+                }
+            }
+            thread::sleep(LEARN_POLL_PERIOD);
+        }
+        None
+    }
+}
+
+// Asks to play the note of each trigger and to move the controller of each parameter, and
+// returns the learnt mapping
+pub fn learn(port: &str) -> Result<MidiMapping, Box<dyn Error>> {
+    let mut midi = Midi::connect(
+        MidiConfig {
+            port: port.to_string(),
+            mapping: MidiMapping::default(),
+        },
+        None,
+    )?;
+    let mut mapping = MidiMapping::default();
+    let timeout = LEARN_TIMEOUT.as_secs();
+
+    for trigger in Trigger::ALL {
+        println!("[MIDI LEARN] Play the note activating {trigger} (skipped after {timeout} s)");
+        let learnt = midi.wait(|m| match m {
+            Message::NoteOn { channel, note } => !mapping
+                .notes
+                .iter()
+                .any(|n| n.note == *note && n.channel == Some(channel + 1)),
+            _ => false,
+        });
+        match learnt {
+            Some(Message::NoteOn { channel, note }) => {
+                println!(
+                    "[MIDI LEARN] {trigger}: note {note} on channel {}",
+                    channel + 1
+                );
+                mapping.notes.push(NoteMapping {
+                    channel: Some(channel + 1),
+                    note,
+                    trigger,
+                });
+            }
+            _ => println!("[MIDI LEARN] {trigger}: skipped"),
+        }
+    }
+
+    for param in Param::ALL {
+        println!("[MIDI LEARN] Move the controller of {param} (skipped after {timeout} s)");
+        let learnt = midi.wait(|m| match m {
+            Message::ControlChange { channel, cc, .. } => !mapping
+                .ccs
+                .iter()
+                .any(|c| c.cc == *cc && c.channel == Some(channel + 1)),
+            _ => false,
+        });
+        match learnt {
+            Some(Message::ControlChange { channel, cc, .. }) => {
+                println!("[MIDI LEARN] {param}: CC {cc} on channel {}", channel + 1);
+                mapping.ccs.push(CcMapping {
+                    channel: Some(channel + 1),
+                    cc,
+                    param,
+                });
+            }
+            _ => println!("[MIDI LEARN] {param}: skipped"),
+        }
+    }
+
+    Ok(mapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::clock::{self, ClockReceiver};
+
+    // Microseconds between two pulses at 120 BPM
+    const PERIOD: u64 = 500_000 / PULSES_PER_BEAT as u64;
+
+    fn clock() -> (MidiClock, ClockReceiver) {
+        let (sender, receiver) = clock::channel();
+        (MidiClock::new(sender), receiver)
+    }
+
+    fn pulses(clock: &mut MidiClock, start: u64, nb_pulses: u64) -> u64 {
+        for i in 0..nb_pulses {
+            clock.process(start + i * PERIOD, &[0xf8]);
+        }
+        start + nb_pulses * PERIOD
+    }
+
+    #[test]
+    fn tempo() {
+        let (mut clock, mut receiver) = clock();
+        // The tempo is unknown after a single pulse
+        pulses(&mut clock, 0, 1);
+        assert!(receiver.now().is_none());
+        pulses(&mut clock, PERIOD, 48);
+        let time = receiver.now().unwrap();
+        assert!((time.bpm - 120.0).abs() < 0.1, "{}", time.bpm);
+        // Beats are only known after a start
+        assert_eq!(time.beat, None);
+    }
+
+    #[test]
+    fn beats_while_running() {
+        let (mut clock, mut receiver) = clock();
+        let end = pulses(&mut clock, 0, 30);
+        clock.process(end, &[0xfa]);
+        pulses(&mut clock, end + PERIOD, 2 * PULSES_PER_BEAT as u64 + 1);
+        let beat = receiver.now().unwrap().beat.unwrap();
+        // Extrapolated until the next pulse at most
+        assert!(
+            (2.0..=2.0 + 1.0 / PULSES_PER_BEAT as f64).contains(&beat),
+            "{beat}"
+        );
+    }
+
+    #[test]
+    fn stop() {
+        let (mut clock, mut receiver) = clock();
+        clock.process(0, &[0xfa]);
+        let end = pulses(&mut clock, 0, 30);
+        clock.process(end, &[0xfc]);
+        assert!(receiver.now().is_none());
+
+        // After a pause, the tempo is measured on the pulses following it
+        let start = end + 10_000_000;
+        pulses(&mut clock, start, 2);
+        let time = receiver.now().unwrap();
+        assert!((time.bpm - 120.0).abs() < 0.1, "{}", time.bpm);
+        assert_eq!(time.beat, None);
+    }
+
+    #[test]
+    fn parse_messages() {
+        assert_eq!(
+            Message::parse(&[0x90, 60, 100]),
+            Some(Message::NoteOn {
+                channel: 0,
+                note: 60
+            })
+        );
+        // A note-on with a null velocity is a note-off
+        assert_eq!(Message::parse(&[0x93, 60, 0]), None);
+        for channel in 0..16 {
+            assert_eq!(
+                Message::parse(&[0xb0 | channel, 7, 127]),
+                Some(Message::ControlChange {
+                    channel,
+                    cc: 7,
+                    value: 127
+                })
+            );
+        }
+        for bytes in [
+            &[][..],
+            &[0x90, 60],
+            &[0xb0, 7],
+            &[0x80, 60, 100],
+            &[0xe0, 0, 64],
+            &[0xf8],
+            &[0x90, 60, 100, 0],
+        ] {
+            assert_eq!(Message::parse(bytes), None, "{bytes:?}");
+        }
+    }
+
+    #[test]
+    fn channels() {
+        assert!(matches_channel(None, 0));
+        assert!(matches_channel(None, 15));
+        // The mapping channels start at 1
+        assert!(matches_channel(Some(1), 0));
+        assert!(matches_channel(Some(16), 15));
+        assert!(!matches_channel(Some(1), 1));
+        assert!(!matches_channel(Some(0), 0));
+    }
+}
@@ -0,0 +1,138 @@
+use crate::audio::{self, AudioConfig};
+use crate::control::Controls;
+use crate::display::{Display, Gpu, OutputConfig};
+use crate::target::{Offscreen, RenderTarget};
+use crate::window::WindowSize;
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::rc::Rc;
+
+pub struct RenderConfig {
+    // Directory of the PNG frames
+    pub out: PathBuf,
+    pub fps: u32,
+    pub size: WindowSize,
+    // Video encoded by ffmpeg from the frames and the audio file
+    pub video: Option<PathBuf>,
+    // Rendered duration in seconds, the whole audio file by default
+    pub duration: Option<f32>,
+    // The renders with the same seed make the same random choices
+    pub seed: Option<u64>,
+}
+
+// Starts ffmpeg, reading the raw RGBA frames from its standard input
+fn spawn_ffmpeg(
+    video: &Path,
+    audio: &Path,
+    size: WindowSize,
+    fps: u32,
+) -> Result<Child, Box<dyn Error>> {
+    Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error"])
+        .args(["-f", "rawvideo", "-pixel_format", "rgba"])
+        .args(["-video_size", &format!("{}x{}", size.width, size.height)])
+        .args(["-framerate", &fps.to_string(), "-i", "-"])
+        .arg("-i")
+        .arg(audio)
+        .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+        .args(["-c:a", "aac", "-shortest"])
+        .arg(video)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run ffmpeg: {e}").into())
+}
+
+// Renders the first display with a fixed timestep, the audio file being analysed in lockstep
+// with the frames
+pub async fn render(
+    output_config: OutputConfig,
+    audio_config: AudioConfig,
+    render_config: RenderConfig,
+) -> Result<(), Box<dyn Error>> {
+    let display_config = output_config
+        .displays
+        .first()
+        .ok_or("No display to render")?;
+    let mut audio = audio::Offline::new(&audio_config)?;
+
+    let (gpu, _) = Gpu::new(&[], output_config.force_fallback_adapter).await?;
+    let gpu = Rc::new(gpu);
+    let RenderConfig {
+        out,
+        fps,
+        size,
+        video,
+        duration,
+        seed,
+    } = render_config;
+    let offscreen = Offscreen::new(gpu.device(), [size.width, size.height]);
+    let nb_bands = audio.nb_bands();
+    let mut display = Display::new(
+        gpu,
+        RenderTarget::Offscreen(offscreen),
+        [size.width, size.height],
+        display_config,
+        &output_config.shows,
+        nb_bands,
+        seed,
+        None,
+    )?;
+
+    fs_err::create_dir_all(&out)?;
+    let mut ffmpeg = match (&video, &audio_config.file) {
+        (Some(v), Some(a)) => Some(spawn_ffmpeg(v, a, size, fps)?),
+        _ => None,
+    };
+
+    let duration = duration
+        .or(audio.duration())
+        .ok_or("The duration of the audio file is unknown, set --duration")?;
+    let nb_frames = (duration * fps as f32).ceil() as u32;
+    println!(
+        "[RENDER] {} frames of {}x{} at {} fps to {}",
+        nb_frames,
+        size.width,
+        size.height,
+        fps,
+        out.display()
+    );
+
+    let controls = Controls::default();
+    for i in 0..nb_frames {
+        let time = i as f32 / fps as f32;
+        let audio_frames = audio.advance(time)?;
+        display.update(time, &audio_frames, &controls);
+        display.render()?;
+
+        let pixels = display.read_frame()?;
+        let path = out.join(format!("frame_{i:06}.png"));
+        image::save_buffer(
+            &path,
+            &pixels,
+            size.width,
+            size.height,
+            image::ColorType::Rgba8,
+        )
+        .map_err(|e| format!("Failed to write the frame {}: {e}", path.display()))?;
+        if let Some(stdin) = ffmpeg.as_mut().and_then(|f| f.stdin.as_mut()) {
+            stdin
+                .write_all(&pixels)
+                .map_err(|e| format!("Failed to send a frame to ffmpeg: {e}"))?;
+        }
+    }
+
+    if let Some(mut f) = ffmpeg {
+        // ffmpeg stops at the end of its input
+        drop(f.stdin.take());
+        let status = f.wait()?;
+        if !status.success() {
+            return Err(format!("ffmpeg failed ({status})").into());
+        }
+    }
+    if let Some(v) = &video {
+        println!("[RENDER] Video encoded to {}", v.display());
+    }
+    Ok(())
+}
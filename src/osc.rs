@@ -0,0 +1,356 @@
+use crate::audio;
+use crate::control::Controls;
+use std::error::Error;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+// Largest UDP payload
+const PACKET_SIZE: usize = 65507;
+const ADDRESS_PREFIX: &str = "/gseq/";
+const BUNDLE_TAG: &[u8] = b"#bundle\0";
+
+// Address of the OSC server and of the receivers of the audio analysis
+pub struct OscConfig {
+    pub listen: SocketAddr,
+    pub broadcast: Option<SocketAddr>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Arg {
+    Int(i32),
+    Float(f32),
+    Str(String),
+    Bool(bool),
+}
+
+impl Arg {
+    fn as_f32(&self) -> Option<f32> {
+        match self {
+            Arg::Int(i) => Some(*i as f32),
+            Arg::Float(f) => Some(*f),
+            Arg::Bool(b) => Some(*b as u8 as f32),
+            Arg::Str(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Message {
+    address: String,
+    args: Vec<Arg>,
+}
+
+// Null-terminated string padded to a multiple of 4 bytes
+fn read_string(packet: &[u8], pos: &mut usize) -> Option<String> {
+    let rest = packet.get(*pos..)?;
+    let len = rest.iter().position(|b| *b == 0)?;
+    let s = std::str::from_utf8(&rest[..len]).ok()?.to_string();
+    *pos += (len + 4) & !3;
+    Some(s)
+}
+
+fn read_bytes<const N: usize>(packet: &[u8], pos: &mut usize) -> Option<[u8; N]> {
+    let bytes = packet.get(*pos..*pos + N)?.try_into().ok()?;
+    *pos += N;
+    Some(bytes)
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(s.as_bytes());
+    buf.resize((buf.len() + 4) & !3, 0);
+}
+
+// Decodes a packet, the messages of the bundles are flattened
+fn decode(packet: &[u8], messages: &mut Vec<Message>) -> Option<()> {
+    if let Some(content) = packet.strip_prefix(BUNDLE_TAG) {
+        // Skip the time tag, the messages are applied immediately
+        let mut pos = 8;
+        while pos < content.len() {
+            let size = i32::from_be_bytes(read_bytes(content, &mut pos)?);
+            let end = pos.checked_add(usize::try_from(size).ok()?)?;
+            decode(content.get(pos..end)?, messages)?;
+            pos = end;
+        }
+        return Some(());
+    }
+
+    let mut pos = 0;
+    let address = read_string(packet, &mut pos)?;
+    // The type tags are optional in old implementations
+    let tags = if pos < packet.len() {
+        read_string(packet, &mut pos)?
+    } else {
+        String::from(",")
+    };
+
+    let mut args = vec![];
+    for t in tags.strip_prefix(',')?.chars() {
+        args.push(match t {
+            'i' => Arg::Int(i32::from_be_bytes(read_bytes(packet, &mut pos)?)),
+            'h' => Arg::Int(i64::from_be_bytes(read_bytes(packet, &mut pos)?) as i32),
+            'f' => Arg::Float(f32::from_be_bytes(read_bytes(packet, &mut pos)?)),
+            'd' => Arg::Float(f64::from_be_bytes(read_bytes(packet, &mut pos)?) as f32),
+            's' | 'S' => Arg::Str(read_string(packet, &mut pos)?),
+            'T' => Arg::Bool(true),
+            'F' => Arg::Bool(false),
+            _ => return None,
+        });
+    }
+    messages.push(Message { address, args });
+    Some(())
+}
+
+fn encode(address: &str, args: &[f32]) -> Vec<u8> {
+    let mut buf = vec![];
+    write_string(&mut buf, address);
+    let tags: String = std::iter::once(',')
+        .chain(args.iter().map(|_| 'f'))
+        .collect();
+    write_string(&mut buf, &tags);
+    for a in args {
+        buf.extend_from_slice(&a.to_be_bytes());
+    }
+    buf
+}
+
+fn apply_message(message: &Message, controls: &mut Controls) -> Result<(), String> {
+    let path = message
+        .address
+        .strip_prefix(ADDRESS_PREFIX)
+        .ok_or("unknown address")?;
+    let (command, name) = match path.split_once('/') {
+        Some((c, n)) => (c, Some(n)),
+        None => (path, None),
+    };
+    let value = message.args.first().and_then(Arg::as_f32);
+
+    match (command, name, message.args.as_slice()) {
+        ("show", None, [Arg::Str(s)]) => controls.show = Some(s.clone()),
+        ("trigger", Some(t), _) => {
+            // Buttons also send a message with a null value when they are released
+            if value.is_none_or(|v| v > 0.0) {
+                controls.triggers.push(t.parse()?);
+            }
+        }
+        ("param", Some(p), [_]) => controls
+            .params
+            .set(p.parse()?, value.ok_or("expected a number")?),
+        ("palette", None, [_]) => {
+            controls.palette = Some(value.ok_or("expected a number")? as usize)
+        }
+        ("blackout", None, [_]) => controls.blackout = value.ok_or("expected a number")? > 0.0,
+        _ => return Err("unknown address or invalid arguments".to_string()),
+    }
+    Ok(())
+}
+
+pub struct Osc {
+    socket: UdpSocket,
+    broadcast: Option<SocketAddr>,
+    buffer: Vec<u8>,
+}
+
+impl Osc {
+    pub fn bind(config: &OscConfig) -> Result<Self, Box<dyn Error>> {
+        let socket = UdpSocket::bind(config.listen)?;
+        socket.set_nonblocking(true)?;
+        socket.set_broadcast(true)?;
+        log::info!("Listening to OSC messages on {}", socket.local_addr()?);
+
+        Ok(Osc {
+            socket,
+            broadcast: config.broadcast,
+            buffer: vec![0; PACKET_SIZE],
+        })
+    }
+
+    // Applies the messages received since the previous call
+    pub fn apply(&mut self, controls: &mut Controls) {
+        loop {
+            let (size, from) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(r) => r,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    log::warn!("Failed to receive an OSC packet: {e}");
+                    return;
+                }
+            };
+
+            let mut messages = vec![];
+            if decode(&self.buffer[..size], &mut messages).is_none() {
+                log::warn!("Invalid OSC packet from {from}");
+                continue;
+            }
+            for m in messages {
+                if let Err(e) = apply_message(&m, controls) {
+                    log::warn!("Invalid OSC message {} from {from}: {e}", m.address);
+                }
+            }
+        }
+    }
+
+    // Sends the gains of the analysed frames
+    pub fn broadcast(&self, audio_frames: &[audio::Data]) {
+        let Some(addr) = self.broadcast else {
+            return;
+        };
+        for d in audio_frames {
+            let packet = encode("/gseq/gain", &d.gain);
+            if let Err(e) = self.socket.send_to(&packet, addr) {
+                log::warn!("Failed to send the audio gains to {addr}: {e}");
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{Param, Trigger};
+
+    fn decode_all(packet: &[u8]) -> Option<Vec<Message>> {
+        let mut messages = vec![];
+        decode(packet, &mut messages)?;
+        Some(messages)
+    }
+
+    fn bundle(packets: &[Vec<u8>]) -> Vec<u8> {
+        let mut buf = BUNDLE_TAG.to_vec();
+        buf.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        for p in packets {
+            buf.extend_from_slice(&(p.len() as i32).to_be_bytes());
+            buf.extend_from_slice(p);
+        }
+        buf
+    }
+
+    #[test]
+    fn round_trip() {
+        // Addresses of every length modulo 4, padded differently
+        for address in ["/gseq", "/gseq/", "/gseq/a", "/gseq/ab", "/gseq/gain"] {
+            for args in [vec![], vec![0.5], vec![1.0, -2.5, f32::MAX]] {
+                let packet = encode(address, &args);
+                assert_eq!(packet.len() % 4, 0);
+                let message = Message {
+                    address: address.to_string(),
+                    args: args.iter().map(|a| Arg::Float(*a)).collect(),
+                };
+                assert_eq!(decode_all(&packet), Some(vec![message]));
+            }
+        }
+    }
+
+    #[test]
+    fn decode_types() {
+        let mut packet = vec![];
+        write_string(&mut packet, "/gseq/x");
+        write_string(&mut packet, ",ihdsTF");
+        packet.extend_from_slice(&7_i32.to_be_bytes());
+        packet.extend_from_slice(&(-3_i64).to_be_bytes());
+        packet.extend_from_slice(&0.25_f64.to_be_bytes());
+        write_string(&mut packet, "dark");
+        let messages = decode_all(&packet).unwrap();
+        assert_eq!(
+            messages[0].args,
+            [
+                Arg::Int(7),
+                Arg::Int(-3),
+                Arg::Float(0.25),
+                Arg::Str("dark".to_string()),
+                Arg::Bool(true),
+                Arg::Bool(false),
+            ]
+        );
+
+        // Without type tags
+        let mut packet = vec![];
+        write_string(&mut packet, "/gseq/x");
+        assert_eq!(decode_all(&packet).unwrap()[0].args, []);
+    }
+
+    #[test]
+    fn decode_bundle() {
+        let inner = bundle(&[encode("/gseq/b", &[2.0])]);
+        let packet = bundle(&[encode("/gseq/a", &[1.0]), inner]);
+        let addresses: Vec<_> = decode_all(&packet)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.address)
+            .collect();
+        assert_eq!(addresses, ["/gseq/a", "/gseq/b"]);
+    }
+
+    #[test]
+    fn malformed_packets() {
+        let valid = encode("/gseq/param/speed", &[0.5]);
+        let mut unknown_tag = vec![];
+        write_string(&mut unknown_tag, "/gseq/x");
+        write_string(&mut unknown_tag, ",z");
+        let mut no_comma = vec![];
+        write_string(&mut no_comma, "/gseq/x");
+        write_string(&mut no_comma, "f");
+        no_comma.extend_from_slice(&[0; 4]);
+        let mut negative_size = bundle(&[]);
+        negative_size.extend_from_slice(&(-4_i32).to_be_bytes());
+        let mut oversized = bundle(&[]);
+        oversized.extend_from_slice(&64_i32.to_be_bytes());
+        oversized.extend_from_slice(&valid);
+
+        for packet in [
+            vec![],
+            b"/gseq".to_vec(),
+            vec![0xff, 0xfe, 0, 0],
+            valid[..valid.len() - 1].to_vec(),
+            unknown_tag,
+            no_comma,
+            negative_size,
+            oversized,
+            bundle(&[valid[..8].to_vec()]),
+            BUNDLE_TAG[..4].to_vec(),
+        ] {
+            assert_eq!(decode_all(&packet), None, "{packet:?}");
+        }
+    }
+
+    #[test]
+    fn apply_messages() {
+        let mut controls = Controls::default();
+        let message = |address: &str, args: Vec<Arg>| Message {
+            address: address.to_string(),
+            args,
+        };
+
+        apply_message(
+            &message("/gseq/param/speed", vec![Arg::Float(0.8)]),
+            &mut controls,
+        )
+        .unwrap();
+        assert_eq!(controls.params.get(Param::Speed), 0.8);
+
+        apply_message(
+            &message("/gseq/show", vec![Arg::Str("dark".into())]),
+            &mut controls,
+        )
+        .unwrap();
+        assert_eq!(controls.show.as_deref(), Some("dark"));
+
+        // The release of a button does not trigger
+        let trigger = "/gseq/trigger/disk";
+        apply_message(&message(trigger, vec![Arg::Int(0)]), &mut controls).unwrap();
+        apply_message(&message(trigger, vec![Arg::Int(1)]), &mut controls).unwrap();
+        apply_message(&message(trigger, vec![]), &mut controls).unwrap();
+        assert_eq!(controls.triggers, [Trigger::Disk, Trigger::Disk]);
+
+        for (address, args) in [
+            ("/other/show", vec![Arg::Str("dark".into())]),
+            ("/gseq/show", vec![Arg::Float(1.0)]),
+            ("/gseq/param/zoom", vec![Arg::Float(1.0)]),
+            ("/gseq/param/speed", vec![Arg::Str("fast".into())]),
+            ("/gseq/trigger/none", vec![]),
+            ("/gseq/blackout", vec![]),
+        ] {
+            assert!(apply_message(&message(address, args), &mut controls).is_err());
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use embedded_graphics::mono_font::{ascii::FONT_6X10, MonoTextStyle};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::{Baseline, Text};
+use std::convert::Infallible;
+use wgpu::util::DeviceExt;
+
+const MAX_COLUMNS: usize = 120;
+const MAX_LINES: usize = 48;
+// Blank border around the text, in texels
+const PADDING: u32 = 4;
+// Size of a texel of the text, in pixels
+const TEXT_SCALE: u32 = 2;
+// Distance between the text and the top left corner of the window, in pixels
+const MARGIN: u32 = 16;
+
+// Monochrome image on which the text is drawn
+struct TextImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl OriginDimensions for TextImage {
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
+
+impl DrawTarget for TextImage {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(p, c) in pixels {
+            let (x, y) = (p.x as u32, p.y as u32);
+            if p.x >= 0 && p.y >= 0 && x < self.width && y < self.height {
+                self.pixels[(y * self.width + x) as usize] = if c.is_on() { 255 } else { 0 };
+            }
+        }
+        Ok(())
+    }
+}
+
+// Splits the text into lines of at most MAX_COLUMNS characters
+fn wrap(text: &str) -> Vec<String> {
+    let mut lines = vec![];
+    for line in text.replace('\t', "    ").lines() {
+        let chars: Vec<_> = line.chars().collect();
+        if chars.is_empty() {
+            lines.push(String::new());
+        }
+        lines.extend(chars.chunks(MAX_COLUMNS).map(|c| c.iter().collect()));
+    }
+    lines.truncate(MAX_LINES);
+    lines
+}
+
+// Text drawn over the show, in the top left corner of the window
+pub struct Overlay {
+    render_pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    rect_buffer: wgpu::Buffer,
+    // None when there is no text
+    bind_group: Option<wgpu::BindGroup>,
+    // Size of the text image, in texels
+    size: [u32; 2],
+}
+
+impl Overlay {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("overlay_bind_group_layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overlay Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Overlay Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shader/overlay.wgsl").into()),
+        });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overlay Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let rect_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Overlay Rect Buffer"),
+            contents: bytemuck::cast_slice(&[0.0f32; 4]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Overlay {
+            render_pipeline,
+            bind_group_layout,
+            rect_buffer,
+            bind_group: None,
+            size: [0, 0],
+        }
+    }
+
+    // Nothing is drawn when the text is empty
+    pub fn set_text(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        text: &str,
+        resolution: [u32; 2],
+    ) {
+        let lines = wrap(text);
+        let columns = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        if columns == 0 {
+            self.bind_group = None;
+            return;
+        }
+
+        let font = &FONT_6X10;
+        let mut image = TextImage {
+            width: columns as u32 * font.character_size.width + 2 * PADDING,
+            height: lines.len() as u32 * font.character_size.height + 2 * PADDING,
+            pixels: vec![],
+        };
+        image.pixels = vec![0; (image.width * image.height) as usize];
+        let style = MonoTextStyle::new(font, BinaryColor::On);
+        for (i, line) in lines.iter().enumerate() {
+            let position = Point::new(
+                PADDING as i32,
+                (PADDING + i as u32 * font.character_size.height) as i32,
+            );
+            let _ = Text::with_baseline(line, position, style, Baseline::Top).draw(&mut image);
+        }
+
+        let size = wgpu::Extent3d {
+            width: image.width,
+            height: image.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("overlay_texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            },
+            &image.pixels,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.rect_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+            ],
+            label: Some("overlay_bind_group"),
+        }));
+        self.size = [image.width, image.height];
+        self.resize(queue, resolution);
+    }
+
+    // Places the text in the window
+    pub fn resize(&self, queue: &wgpu::Queue, resolution: [u32; 2]) {
+        let [width, height] = resolution.map(|r| r.max(1) as f32);
+        let left = -1.0 + 2.0 * MARGIN as f32 / width;
+        let top = 1.0 - 2.0 * MARGIN as f32 / height;
+        let rect = [
+            left,
+            top,
+            left + 2.0 * (self.size[0] * TEXT_SCALE) as f32 / width,
+            top - 2.0 * (self.size[1] * TEXT_SCALE) as f32 / height,
+        ];
+        queue.write_buffer(&self.rect_buffer, 0, bytemuck::cast_slice(&rect));
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if let Some(bind_group) = &self.bind_group {
+            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+    }
+}
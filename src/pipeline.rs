@@ -1,17 +1,42 @@
+use std::ops::Range;
 use thiserror::Error;
 
 use crate::instance::InstanceRaw;
 use crate::model::InstanceModel;
 use crate::model::Model;
 use crate::model::ModelError;
+use crate::shader::{self, ShaderInterface};
 use crate::texture::Texture;
 
+// Bind group layouts of the displays bound by the pipelines of group 0 and by the post-processing
+pub const GROUP_0_BIND_GROUPS: [usize; 3] = [0, 1, 3];
+pub const POST_BIND_GROUPS: [usize; 2] = [0, 2];
+
 #[derive(Error, Debug)]
 pub enum PipelineError {
     #[error("Failed to read shader [{}: {}]\n\t{0}", file!(), line!())]
     Reading(#[from] std::io::Error),
     #[error("Failed to load model [{}: {}]\n\t{0}", file!(), line!())]
     ModelLoading(#[from] ModelError),
+    #[error("Failed to compile shader [{}: {}]\n\t{0}", file!(), line!())]
+    ShaderCompilation(String),
+    #[error("Invalid shader [{}: {}]\n\t{file}:{line}:{column}\n{message}", file!(), line!())]
+    ShaderValidation {
+        file: String,
+        line: u32,
+        column: u32,
+        // Byte range of the error in the source
+        span: Range<usize>,
+        message: String,
+    },
+}
+
+pub fn group_0_interface() -> ShaderInterface {
+    ShaderInterface::new(&GROUP_0_BIND_GROUPS, &[Model::desc(), InstanceRaw::desc()])
+}
+
+pub fn post_interface() -> ShaderInterface {
+    ShaderInterface::new(&POST_BIND_GROUPS, &[Model::desc()])
 }
 
 pub struct Pipeline {
@@ -26,7 +51,7 @@ pub struct PipelineGroup {
 
 pub struct PipelinePost {
     pub layout: LayoutInner,
-    pub render_pipeline: wgpu::RenderPipeline,
+    render_pipeline: wgpu::RenderPipeline,
     model: Model, //quad
 }
 
@@ -59,17 +84,57 @@ impl PipelineGroup {
     pub fn add_pipeline(
         &mut self,
         instance_models: Vec<InstanceModel>,
-        shader_path: &str,
+        shader: &str,
+        shader_file: &str,
         device: &wgpu::Device,
-        config: &wgpu::SurfaceConfiguration,
     ) -> Result<(), PipelineError> {
+        shader::validate(shader, shader_file, Some(&self.interface()))?;
+        let shader = &shader::with_viewport(shader);
+        let render_pipeline = validated(device, || self.create_render_pipeline(shader, device))?;
+        let pipeline = Pipeline {
+            render_pipeline,
+            instance_models,
+        };
+
+        self.pipelines.push(pipeline);
+
+        Ok(())
+    }
+
+    // Replaces the shader of a pipeline, the previous one being kept if the new one is invalid
+    pub fn reload_pipeline(
+        &mut self,
+        index: usize,
+        shader: &str,
+        shader_file: &str,
+        device: &wgpu::Device,
+    ) -> Result<(), PipelineError> {
+        shader::validate(shader, shader_file, Some(&self.interface()))?;
+        let shader = &shader::with_viewport(shader);
+        let render_pipeline = validated(device, || self.create_render_pipeline(shader, device))?;
+        self.pipelines[index].render_pipeline = render_pipeline;
+        Ok(())
+    }
+
+    fn interface(&self) -> ShaderInterface {
+        ShaderInterface::new(
+            self.layout.get_bind_group_indices(),
+            &[Model::desc(), InstanceRaw::desc()],
+        )
+    }
+
+    fn create_render_pipeline(
+        &self,
+        shader_path: &str,
+        device: &wgpu::Device,
+    ) -> wgpu::RenderPipeline {
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
             source: wgpu::ShaderSource::Wgsl(shader_path.into()),
         });
 
         let pipeline_layout = self.layout.get_pipeline_layout();
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Render Pipeline"),
             layout: Some(pipeline_layout),
             vertex: wgpu::VertexState {
@@ -81,7 +146,8 @@ impl PipelineGroup {
                 module: &shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
+                    // The pipelines of the groups render to the framebuffer
+                    format: Texture::FRAMEBUFFER_FORMAT,
                     blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -112,16 +178,17 @@ impl PipelineGroup {
                 alpha_to_coverage_enabled: false,
             },
             multiview: None,
-        });
-
-        let pipeline = Pipeline {
-            render_pipeline,
-            instance_models,
-        };
-
-        self.pipelines.push(pipeline);
+        })
+    }
+}
 
-        Ok(())
+// Runs f, returning the validation error it raised
+fn validated<T>(device: &wgpu::Device, f: impl FnOnce() -> T) -> Result<T, PipelineError> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = f();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(e) => Err(PipelineError::ShaderCompilation(e.to_string())),
+        None => Ok(value),
     }
 }
 
@@ -185,8 +252,13 @@ impl PipelinePost {
         bind_group_indices: Vec<usize>,
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
-        shader_path: &str,
+        shader: &str,
+        shader_file: &str,
     ) -> Result<Self, PipelineError> {
+        let interface = ShaderInterface::new(&bind_group_indices, &[Model::desc()]);
+        shader::validate(shader, shader_file, Some(&interface))?;
+        let shader = &shader::with_viewport(shader);
+
         let bind_groups: Vec<_> = bind_group_indices
             .iter()
             .map(|i| bind_group_layout[*i])
@@ -198,49 +270,9 @@ impl PipelinePost {
             push_constant_ranges: &[],
         });
 
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(shader_path.into()),
-        });
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Post Processing Render Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[Model::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
-                // or Features::POLYGON_MODE_POINT
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
+        let render_pipeline = validated(device, || {
+            create_post_render_pipeline(&pipeline_layout, shader, device, config)
+        })?;
 
         let layout = LayoutInner {
             pipeline_layout,
@@ -254,6 +286,23 @@ impl PipelinePost {
         })
     }
 
+    // Replaces the shader, the previous one being kept if the new one is invalid
+    pub fn reload(
+        &mut self,
+        shader: &str,
+        shader_file: &str,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> Result<(), PipelineError> {
+        let interface = ShaderInterface::new(&self.layout.bind_group_indices, &[Model::desc()]);
+        shader::validate(shader, shader_file, Some(&interface))?;
+        let shader = &shader::with_viewport(shader);
+        self.render_pipeline = validated(device, || {
+            create_post_render_pipeline(&self.layout.pipeline_layout, shader, device, config)
+        })?;
+        Ok(())
+    }
+
     pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
         render_pass.set_pipeline(&self.render_pipeline);
         for mesh in &self.model.meshes {
@@ -267,3 +316,54 @@ impl PipelinePost {
         &self.layout.bind_group_indices
     }
 }
+
+fn create_post_render_pipeline(
+    pipeline_layout: &wgpu::PipelineLayout,
+    shader_path: &str,
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: wgpu::ShaderSource::Wgsl(shader_path.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Post Processing Render Pipeline"),
+        layout: Some(pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[Model::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: config.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
+            // or Features::POLYGON_MODE_POINT
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // Requires Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // Requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
@@ -0,0 +1,422 @@
+use crate::pipeline::{self, PipelineError};
+use crate::show::ShowLibrary;
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::{Files, SimpleFiles};
+use codespan_reporting::term::{self, termcolor::NoColor};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::{AddressSpace, Binding, Module, ShaderStage, Span, TypeInner, WithSpan};
+use std::error::Error;
+use std::ops::Range;
+use std::path::Path;
+use thiserror::Error;
+
+// Resource bound to a binding of a bind group layout
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resource {
+    Uniform,
+    Storage,
+    Texture,
+    Sampler,
+}
+
+// Viewport uniform and its helpers, shared by the shaders of the shows
+const VIEWPORT: &str = include_str!("../shader/viewport.wgsl");
+const VIEWPORT_FILE: &str = "viewport.wgsl";
+
+// Bindings of the bind group layouts created by the displays
+const BIND_GROUP_LAYOUTS: [&[Resource]; 4] = {
+    use Resource::*;
+    [
+        // Audio, time, size, spectrum, waveform, params and viewport
+        &[
+            Storage, Uniform, Uniform, Texture, Texture, Uniform, Uniform,
+        ],
+        // Camera
+        &[Uniform],
+        // Framebuffer
+        &[Texture, Sampler],
+        // Texture images
+        &[Texture, Sampler],
+    ]
+};
+
+#[derive(Error, Debug)]
+enum InterfaceError {
+    #[error("missing {0:?} entry point {1}")]
+    MissingEntryPoint(ShaderStage, &'static str),
+    #[error("the group {0} is not bound by the pipeline")]
+    UnboundGroup(u32),
+    #[error("the group {group} has no binding {binding}")]
+    UnknownBinding { group: u32, binding: u32 },
+    #[error("the binding {binding} of the group {group} is a {expected:?}, not a {found:?}")]
+    ResourceMismatch {
+        group: u32,
+        binding: u32,
+        expected: Resource,
+        found: Resource,
+    },
+    #[error("the vertex location {0} is not provided by the vertex buffers")]
+    UnknownLocation(u32),
+}
+
+// What a pipeline binds for its shader
+#[derive(Clone, Debug)]
+pub struct ShaderInterface {
+    // Bind group layouts bound to the groups of the shader
+    bind_group_indices: Vec<usize>,
+    vertex_locations: Vec<u32>,
+}
+
+impl ShaderInterface {
+    pub fn new(bind_group_indices: &[usize], vertex_buffers: &[wgpu::VertexBufferLayout]) -> Self {
+        ShaderInterface {
+            bind_group_indices: bind_group_indices.to_vec(),
+            vertex_locations: vertex_buffers
+                .iter()
+                .flat_map(|b| b.attributes.iter().map(|a| a.shader_location))
+                .collect(),
+        }
+    }
+}
+
+// Error of a shader with the viewport declarations, located in the shader file or in the
+// declarations. labels are byte ranges in the whole source.
+fn shader_error(
+    source: &str,
+    file: &str,
+    message: String,
+    labels: Vec<(Range<usize>, String)>,
+    notes: Vec<String>,
+) -> PipelineError {
+    let mut files = SimpleFiles::new();
+    let viewport = files.add(VIEWPORT_FILE, VIEWPORT);
+    let shader = files.add(file, source);
+    let labels: Vec<_> = labels
+        .into_iter()
+        .map(|(r, m)| match r.start.checked_sub(VIEWPORT.len()) {
+            Some(start) => Label::primary(shader, start..r.end - VIEWPORT.len()).with_message(m),
+            None => Label::primary(viewport, r).with_message(m),
+        })
+        .collect();
+    let location = labels.first().and_then(|l| {
+        let location = files.location(l.file_id, l.range.start).ok()?;
+        Some((l, location))
+    });
+
+    let diagnostic = Diagnostic::error()
+        .with_message(message)
+        .with_labels(labels.clone())
+        .with_notes(notes);
+    let mut writer = NoColor::new(Vec::new());
+    term::emit(&mut writer, &term::Config::default(), &files, &diagnostic)
+        .expect("Failed to format a shader error");
+
+    PipelineError::ShaderValidation {
+        file: location
+            .map_or(file, |(l, _)| files.get(l.file_id).unwrap().name())
+            .to_string(),
+        line: location.map_or(1, |(_, l)| l.line_number as u32),
+        column: location.map_or(1, |(_, l)| l.column_number as u32),
+        span: location.map_or(0..0, |(l, _)| l.range.clone()),
+        message: String::from_utf8_lossy(&writer.into_inner()).into_owned(),
+    }
+}
+
+fn to_pipeline_error<E: Error>(error: WithSpan<E>, source: &str, file: &str) -> PipelineError {
+    let mut notes = vec![];
+    let mut cause: &dyn Error = error.as_inner();
+    while let Some(next) = cause.source() {
+        notes.push(next.to_string());
+        cause = next;
+    }
+    let labels = error
+        .spans()
+        .filter_map(|(s, m)| Some((s.to_range()?, m.clone())))
+        .collect();
+    shader_error(source, file, error.as_inner().to_string(), labels, notes)
+}
+
+fn resource(module: &Module, space: AddressSpace, ty: naga::Handle<naga::Type>) -> Resource {
+    match (space, &module.types[ty].inner) {
+        (AddressSpace::Storage { .. }, _) => Resource::Storage,
+        (AddressSpace::Handle, TypeInner::Sampler { .. }) => Resource::Sampler,
+        (AddressSpace::Handle, _) => Resource::Texture,
+        _ => Resource::Uniform,
+    }
+}
+
+fn check_interface(
+    module: &Module,
+    interface: &ShaderInterface,
+) -> Result<(), WithSpan<InterfaceError>> {
+    for (stage, name) in [
+        (ShaderStage::Vertex, "vs_main"),
+        (ShaderStage::Fragment, "fs_main"),
+    ] {
+        if !module
+            .entry_points
+            .iter()
+            .any(|e| e.stage == stage && e.name == name)
+        {
+            return Err(WithSpan::new(InterfaceError::MissingEntryPoint(
+                stage, name,
+            )));
+        }
+    }
+
+    for (handle, var) in module.global_variables.iter() {
+        let Some(b) = &var.binding else {
+            continue;
+        };
+        let span = module.global_variables.get_span(handle);
+        let error = |e| Err(WithSpan::new(e).with_span(span, "bound here"));
+        let Some(layout) = interface.bind_group_indices.get(b.group as usize) else {
+            return error(InterfaceError::UnboundGroup(b.group));
+        };
+        let Some(expected) = BIND_GROUP_LAYOUTS[*layout].get(b.binding as usize) else {
+            return error(InterfaceError::UnknownBinding {
+                group: b.group,
+                binding: b.binding,
+            });
+        };
+        let found = resource(module, var.space, var.ty);
+        if found != *expected {
+            return error(InterfaceError::ResourceMismatch {
+                group: b.group,
+                binding: b.binding,
+                expected: *expected,
+                found,
+            });
+        }
+    }
+
+    let vertex_inputs = module
+        .entry_points
+        .iter()
+        .filter(|e| e.stage == ShaderStage::Vertex)
+        .flat_map(|e| &e.function.arguments);
+    for arg in vertex_inputs {
+        // The inputs are either arguments or members of a structure
+        let (bindings, span) = match &module.types[arg.ty].inner {
+            TypeInner::Struct { members, .. } => (
+                members.iter().map(|m| &m.binding).collect(),
+                module.types.get_span(arg.ty),
+            ),
+            _ => (vec![&arg.binding], Span::default()),
+        };
+        for binding in bindings {
+            if let Some(Binding::Location { location, .. }) = binding {
+                if !interface.vertex_locations.contains(location) {
+                    let e = WithSpan::new(InterfaceError::UnknownLocation(*location));
+                    return Err(if span.is_defined() {
+                        e.with_span(span, "vertex input")
+                    } else {
+                        e
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Source of a shader of a show with the viewport declarations, prepended as naga resolves the
+// identifiers in the order of their declarations
+pub fn with_viewport(source: &str) -> String {
+    format!("{VIEWPORT}{source}")
+}
+
+// Parses and validates the shader, and checks it against what the pipeline binds when known
+pub fn validate(
+    source: &str,
+    file: &str,
+    interface: Option<&ShaderInterface>,
+) -> Result<(), PipelineError> {
+    let full = with_viewport(source);
+    let module = naga::front::wgsl::parse_str(&full).map_err(|e| {
+        let labels = e.labels().map(|(r, m)| (r, m.to_string())).collect();
+        shader_error(source, file, e.message().to_string(), labels, vec![])
+    })?;
+    Validator::new(ValidationFlags::all(), Capabilities::empty())
+        .validate(&module)
+        .map_err(|e| to_pipeline_error(e, source, file))?;
+    if let Some(i) = interface {
+        check_interface(&module, i).map_err(|e| to_pipeline_error(e, source, file))?;
+    }
+    Ok(())
+}
+
+// Validates the shaders of the directory against the pipelines using them in the built-in shows
+// and in the show files of the directory
+pub fn check_shaders(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let mut shows = ShowLibrary::new(None)?;
+    shows.load_dir(dir)?;
+
+    let mut paths = fs_err::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|p| p.extension().is_some_and(|e| e == "wgsl"));
+    paths.sort();
+    if paths.is_empty() {
+        return Err(format!("No WGSL shader in {}", dir.display()).into());
+    }
+
+    let mut nb_invalid = 0;
+    for path in &paths {
+        let file = path.file_name().unwrap_or_default().to_string_lossy();
+        let source = fs_err::read_to_string(path)?;
+        let mut interfaces = vec![];
+        if shows
+            .iter()
+            .any(|s| s.pipelines.iter().any(|p| p.shader_file == file))
+        {
+            interfaces.push(pipeline::group_0_interface());
+        }
+        if shows.iter().any(|s| s.post_file == file) {
+            interfaces.push(pipeline::post_interface());
+        }
+
+        // The shaders used by no show are only parsed and validated
+        let result = if interfaces.is_empty() {
+            validate(&source, &file, None)
+        } else {
+            interfaces
+                .iter()
+                .try_for_each(|i| validate(&source, &file, Some(i)))
+        };
+        match result {
+            Ok(()) => println!("[OK] {file}"),
+            Err(e) => {
+                nb_invalid += 1;
+                eprintln!("[INVALID] {e}");
+            }
+        }
+    }
+
+    if nb_invalid > 0 {
+        return Err(format!("{nb_invalid} of the {} shaders are invalid", paths.len()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERTEX: &str = "
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> @builtin(position) vec4<f32> {
+    return crop(vec4<f32>(in.position, 1.0));
+}
+";
+
+    const FRAGMENT: &str = "
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
+";
+
+    fn interface() -> ShaderInterface {
+        ShaderInterface {
+            bind_group_indices: vec![0, 1],
+            vertex_locations: vec![0, 1],
+        }
+    }
+
+    fn check(source: &str) -> Result<(), InterfaceError> {
+        let module = naga::front::wgsl::parse_str(&with_viewport(source)).unwrap();
+        check_interface(&module, &interface()).map_err(|e| e.into_inner())
+    }
+
+    #[test]
+    fn valid_interface() {
+        let globals = "
+@group(0) @binding(1) var<uniform> time: f32;
+@group(0) @binding(3) var spectrum: texture_1d<f32>;
+@group(1) @binding(0) var<uniform> camera: mat4x4<f32>;
+";
+        let source = format!("{globals}{VERTEX}{FRAGMENT}");
+        assert!(check(&source).is_ok());
+        assert!(validate(&source, "test.wgsl", Some(&interface())).is_ok());
+    }
+
+    #[test]
+    fn missing_entry_point() {
+        assert!(matches!(
+            check(VERTEX),
+            Err(InterfaceError::MissingEntryPoint(
+                ShaderStage::Fragment,
+                "fs_main"
+            ))
+        ));
+        let source = format!("{VERTEX}{}", FRAGMENT.replace("fs_main", "main"));
+        assert!(matches!(
+            check(&source),
+            Err(InterfaceError::MissingEntryPoint(
+                ShaderStage::Fragment,
+                "fs_main"
+            ))
+        ));
+    }
+
+    #[test]
+    fn invalid_bindings() {
+        let source = format!("@group(0) @binding(3) var<uniform> x: f32;\n{VERTEX}{FRAGMENT}");
+        assert!(matches!(
+            check(&source),
+            Err(InterfaceError::ResourceMismatch {
+                group: 0,
+                binding: 3,
+                expected: Resource::Texture,
+                found: Resource::Uniform,
+            })
+        ));
+        // The error is located in the shader file, after the viewport declarations
+        match validate(&source, "test.wgsl", Some(&interface())) {
+            Err(PipelineError::ShaderValidation { file, line, .. }) => {
+                assert_eq!((file.as_str(), line), ("test.wgsl", 1));
+            }
+            _ => panic!("the resource mismatch is not reported"),
+        }
+
+        let source = format!("@group(2) @binding(0) var<uniform> x: f32;\n{VERTEX}{FRAGMENT}");
+        assert!(matches!(
+            check(&source),
+            Err(InterfaceError::UnboundGroup(2))
+        ));
+        let source = format!("@group(1) @binding(1) var<uniform> x: f32;\n{VERTEX}{FRAGMENT}");
+        assert!(matches!(
+            check(&source),
+            Err(InterfaceError::UnknownBinding {
+                group: 1,
+                binding: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn unknown_vertex_location() {
+        let source = format!("{}{FRAGMENT}", VERTEX.replace("location(1)", "location(2)"));
+        assert!(matches!(
+            check(&source),
+            Err(InterfaceError::UnknownLocation(2))
+        ));
+        let source = format!(
+            "@vertex
+fn vs_main(@location(3) position: vec3<f32>) -> @builtin(position) vec4<f32> {{
+    return vec4<f32>(position, 1.0);
+}}
+{FRAGMENT}"
+        );
+        assert!(matches!(
+            check(&source),
+            Err(InterfaceError::UnknownLocation(3))
+        ));
+    }
+}
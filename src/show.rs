@@ -0,0 +1,498 @@
+use crate::color::Color;
+use crate::control::Trigger;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use thiserror::Error;
+
+// Shows built into the program
+const BUILTIN_SHOWS: [&str; 2] = [
+    include_str!("../show/marius-julien.toml"),
+    include_str!("../show/lua.toml"),
+];
+
+// Assets built into the program, available to every show file
+const BUILTIN_ASSETS: [(&str, &[u8]); 14] = [
+    (
+        "shader/vs_0/2d_full.wgsl",
+        include_bytes!("../shader/vs_0/2d_full.wgsl"),
+    ),
+    (
+        "shader/vs_0/2d_logo.wgsl",
+        include_bytes!("../shader/vs_0/2d_logo.wgsl"),
+    ),
+    (
+        "shader/vs_0/2d_noise.wgsl",
+        include_bytes!("../shader/vs_0/2d_noise.wgsl"),
+    ),
+    (
+        "shader/vs_0/2d_transparent.wgsl",
+        include_bytes!("../shader/vs_0/2d_transparent.wgsl"),
+    ),
+    (
+        "shader/vs_0/3d.wgsl",
+        include_bytes!("../shader/vs_0/3d.wgsl"),
+    ),
+    (
+        "shader/vs_0/3d_noise_geometry.wgsl",
+        include_bytes!("../shader/vs_0/3d_noise_geometry.wgsl"),
+    ),
+    (
+        "shader/vs_0/post_0.wgsl",
+        include_bytes!("../shader/vs_0/post_0.wgsl"),
+    ),
+    (
+        "shader/vs_0/post_1.wgsl",
+        include_bytes!("../shader/vs_0/post_1.wgsl"),
+    ),
+    (
+        "shader/vs_0/wallpaper_noise_0.wgsl",
+        include_bytes!("../shader/vs_0/wallpaper_noise_0.wgsl"),
+    ),
+    (
+        "shader/vs_0/wallpaper_noise_1.wgsl",
+        include_bytes!("../shader/vs_0/wallpaper_noise_1.wgsl"),
+    ),
+    ("models/cube.obj", include_bytes!("../models/cube.obj")),
+    (
+        "models/icosphere.obj",
+        include_bytes!("../models/icosphere.obj"),
+    ),
+    (
+        "models/mfroom_3d.obj",
+        include_bytes!("../models/mfroom_3d.obj"),
+    ),
+    (
+        "models/pyramide.obj",
+        include_bytes!("../models/pyramide.obj"),
+    ),
+];
+
+const NB_ACTIVE_PIPELINES: usize = 3;
+
+#[derive(Error, Debug)]
+pub enum ShowError {
+    #[error("Failed to read show file [{}: {}]\n\t{0}", file!(), line!())]
+    Reading(#[from] std::io::Error),
+    #[error("Failed to parse show file [{}: {}]\n\t{0}", file!(), line!())]
+    Parsing(#[from] toml::de::Error),
+    #[error("Failed to find a show asset [{}: {}]\n\t{0}", file!(), line!())]
+    MissingAsset(String),
+    #[error("Invalid show [{}: {}]\n\t{0}", file!(), line!())]
+    Invalid(String),
+    #[error("Unknown show [{}: {}]\n\t{0}", file!(), line!())]
+    Unknown(String),
+}
+
+// Role of a pipeline in the show
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PipelineKind {
+    // Shaded with the background colors of the palette
+    Background,
+    // Grows while the input is silent
+    Logo,
+    #[serde(rename = "noise-3d")]
+    Noise3d,
+    Full,
+    Disk,
+    #[serde(rename = "wf-3d")]
+    Wf3d,
+}
+
+impl PipelineKind {
+    // The dynamic pipelines are activated by a trigger
+    pub fn trigger(self) -> Option<Trigger> {
+        match self {
+            PipelineKind::Background | PipelineKind::Logo => None,
+            PipelineKind::Noise3d => Some(Trigger::Noise3d),
+            PipelineKind::Full => Some(Trigger::Full),
+            PipelineKind::Disk => Some(Trigger::Disk),
+            PipelineKind::Wf3d => Some(Trigger::Wf3d),
+        }
+    }
+
+    fn default_threshold(self) -> f32 {
+        match self {
+            PipelineKind::Wf3d => 2.0,
+            _ => 1.5,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ShowFile {
+    name: String,
+    // Post-processing shader
+    post: String,
+    #[serde(default)]
+    default_palette: usize,
+    // Maximum number of dynamic pipelines displayed at the same time
+    #[serde(default = "default_active_pipelines")]
+    active_pipelines: usize,
+    #[serde(rename = "palette")]
+    palettes: Vec<Palette>,
+    #[serde(rename = "pipeline")]
+    pipelines: Vec<PipelineFile>,
+}
+
+fn default_active_pipelines() -> usize {
+    NB_ACTIVE_PIPELINES
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct PipelineFile {
+    kind: PipelineKind,
+    shader: String,
+    models: Vec<ModelFile>,
+    threshold: Option<f32>,
+    #[serde(default)]
+    active: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ModelFile {
+    // quad, disk or the path of an obj file
+    model: String,
+    #[serde(default = "default_instances")]
+    instances: usize,
+}
+
+fn default_instances() -> usize {
+    1
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Palette {
+    // Colors of the activated instances
+    pub colors: Vec<Color>,
+    // The two colors between which the background shades
+    pub background: [Color; 2],
+}
+
+#[derive(Clone)]
+pub enum ModelSource {
+    Quad,
+    Disk,
+    Obj(Vec<u8>),
+}
+
+#[derive(Clone)]
+pub struct ModelDescription {
+    pub source: ModelSource,
+    pub instances: usize,
+}
+
+#[derive(Clone)]
+pub struct PipelineDescription {
+    pub kind: PipelineKind,
+    // Source of the shader
+    pub shader: String,
+    // Name of the shader file, without its directory
+    pub shader_file: String,
+    pub models: Vec<ModelDescription>,
+    // Gain of the band above which the pipeline is activated
+    pub threshold: f32,
+    // Displayed when the show starts
+    pub active: bool,
+}
+
+// Visual show, with its assets loaded
+#[derive(Clone)]
+pub struct Show {
+    pub name: String,
+    // Source of the post-processing shader
+    pub post: String,
+    pub post_file: String,
+    pub palettes: Vec<Palette>,
+    pub default_palette: usize,
+    pub active_pipelines: usize,
+    pub pipelines: Vec<PipelineDescription>,
+}
+
+// Reads an asset relative to the directory of the show file, or a built-in asset
+fn read_asset(path: &str, dir: Option<&Path>) -> Result<Vec<u8>, ShowError> {
+    if let Some(p) = dir.map(|d| d.join(path)).filter(|p| p.is_file()) {
+        return Ok(fs_err::read(p)?);
+    }
+    BUILTIN_ASSETS
+        .iter()
+        .find(|(name, _)| *name == path)
+        .map(|(_, content)| content.to_vec())
+        .ok_or_else(|| ShowError::MissingAsset(path.to_string()))
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+// The shaders of the shader directory replace the ones with the same file names
+fn read_shader(
+    path: &str,
+    dir: Option<&Path>,
+    shader_dir: Option<&Path>,
+) -> Result<String, ShowError> {
+    let content = match shader_dir
+        .map(|d| d.join(file_name(path)))
+        .filter(|p| p.is_file())
+    {
+        Some(p) => fs_err::read(p)?,
+        None => read_asset(path, dir)?,
+    };
+    String::from_utf8(content)
+        .map_err(|_| ShowError::Invalid(format!("the shader {path} is not valid UTF-8")))
+}
+
+impl Show {
+    pub fn load(path: &Path, shader_dir: Option<&Path>) -> Result<Self, ShowError> {
+        let content = fs_err::read_to_string(path)?;
+        Self::parse(&content, path.parent(), shader_dir)
+    }
+
+    // The assets are looked up in dir first
+    fn parse(
+        content: &str,
+        dir: Option<&Path>,
+        shader_dir: Option<&Path>,
+    ) -> Result<Self, ShowError> {
+        let file: ShowFile = toml::from_str(content)?;
+        let invalid = |m: &str| Err(ShowError::Invalid(format!("{}: {m}", file.name)));
+
+        if file.palettes.iter().any(|p| p.colors.is_empty()) {
+            return invalid("a palette has no colors");
+        }
+        if file.default_palette >= file.palettes.len() {
+            return invalid("the default palette is not declared");
+        }
+        if file.active_pipelines == 0 {
+            return invalid("at least one pipeline must be active");
+        }
+        for (i, p) in file.pipelines.iter().enumerate() {
+            if file.pipelines[..i].iter().any(|q| q.kind == p.kind) {
+                return invalid("two pipelines have the same kind");
+            }
+            if p.models.is_empty() || p.models.iter().any(|m| m.instances == 0) {
+                return invalid("a pipeline has no model or a model has no instance");
+            }
+        }
+        if file.pipelines.iter().all(|p| p.kind.trigger().is_none()) {
+            return invalid("the show has no dynamic pipeline");
+        }
+
+        let mut pipelines = vec![];
+        for p in &file.pipelines {
+            let mut models = vec![];
+            for m in &p.models {
+                let source = match m.model.as_str() {
+                    "quad" => ModelSource::Quad,
+                    "disk" => ModelSource::Disk,
+                    path => ModelSource::Obj(read_asset(path, dir)?),
+                };
+                models.push(ModelDescription {
+                    source,
+                    instances: m.instances,
+                });
+            }
+            pipelines.push(PipelineDescription {
+                kind: p.kind,
+                shader: read_shader(&p.shader, dir, shader_dir)?,
+                shader_file: file_name(&p.shader),
+                models,
+                threshold: p.threshold.unwrap_or(p.kind.default_threshold()),
+                active: p.active,
+            });
+        }
+
+        Ok(Show {
+            post: read_shader(&file.post, dir, shader_dir)?,
+            post_file: file_name(&file.post),
+            name: file.name,
+            palettes: file.palettes,
+            default_palette: file.default_palette,
+            active_pipelines: file.active_pipelines,
+            pipelines,
+        })
+    }
+}
+
+// Shows that can be displayed, by name
+pub struct ShowLibrary {
+    shows: Vec<Rc<Show>>,
+    // Directory of the shaders replacing the ones of the shows
+    shader_dir: Option<PathBuf>,
+}
+
+impl ShowLibrary {
+    // Loads the built-in shows
+    pub fn new(shader_dir: Option<PathBuf>) -> Result<Self, ShowError> {
+        let shows = BUILTIN_SHOWS
+            .iter()
+            .map(|s| Show::parse(s, None, shader_dir.as_deref()).map(Rc::new))
+            .collect::<Result<_, _>>()?;
+        Ok(ShowLibrary { shows, shader_dir })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Show> {
+        self.shows.iter().map(|s| s.as_ref())
+    }
+
+    pub fn shader_dir(&self) -> Option<&Path> {
+        self.shader_dir.as_deref()
+    }
+
+    // Loads the show files (.toml) of the directory, replacing the shows with the same names
+    pub fn load_dir(&mut self, dir: &Path) -> Result<(), ShowError> {
+        let mut paths = fs_err::read_dir(dir)?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+        for path in paths {
+            if path.extension().is_some_and(|e| e == "toml") {
+                self.insert(Show::load(&path, self.shader_dir.as_deref())?);
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, show: Show) {
+        self.shows.retain(|s| s.name != show.name);
+        self.shows.push(Rc::new(show));
+    }
+
+    pub fn get(&self, name: &str) -> Result<Rc<Show>, ShowError> {
+        self.shows
+            .iter()
+            .find(|s| s.name == name)
+            .cloned()
+            .ok_or_else(|| {
+                let names: Vec<_> = self.shows.iter().map(|s| s.name.as_str()).collect();
+                ShowError::Unknown(format!("{name} (available shows: {})", names.join(", ")))
+            })
+    }
+
+    // Replaces the source of a shader file in the shows using it
+    pub fn update_shader(&mut self, file: &str, source: &str) {
+        for show in &mut self.shows {
+            let uses_shader =
+                show.post_file == file || show.pipelines.iter().any(|p| p.shader_file == file);
+            if !uses_shader {
+                continue;
+            }
+            let show = Rc::make_mut(show);
+            if show.post_file == file {
+                show.post = source.to_string();
+            }
+            for p in &mut show.pipelines {
+                if p.shader_file == file {
+                    p.shader = source.to_string();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Valid show, the parts replaced by the tests are on their own lines
+    const SHOW: &str = r##"
+name = "test"
+post = "shader/vs_0/post_0.wgsl"
+default_palette = 1
+active_pipelines = 2
+
+[[palette]]
+colors = ["#9f56ff", "#b582ff"]
+background = ["#9f56ff", "#ff99b6"]
+
+[[palette]]
+colors = ["#1b59ff"]
+background = ["#1b59ff", "#2f0885"]
+
+[[pipeline]]
+kind = "background"
+shader = "shader/vs_0/wallpaper_noise_0.wgsl"
+models = [{ model = "quad" }]
+
+[[pipeline]]
+kind = "disk"
+shader = "shader/vs_0/2d_transparent.wgsl"
+models = [{ model = "disk", instances = 4 }]
+threshold = 1.8
+active = true
+"##;
+
+    fn parse(content: &str) -> Result<Show, ShowError> {
+        Show::parse(content, None, None)
+    }
+
+    #[test]
+    fn builtin_shows() {
+        let library = ShowLibrary::new(None).unwrap();
+        let names: Vec<_> = library.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["marius-julien", "lua"]);
+        for show in library.iter() {
+            assert!(!show.post.is_empty());
+            assert!(show.default_palette < show.palettes.len());
+            assert!(show.pipelines.iter().any(|p| p.kind.trigger().is_some()));
+        }
+        assert!(matches!(library.get("none"), Err(ShowError::Unknown(_))));
+    }
+
+    #[test]
+    fn show() {
+        let show = parse(SHOW).unwrap();
+        assert_eq!(show.post_file, "post_0.wgsl");
+        assert_eq!(show.palettes.len(), 2);
+        assert_eq!(show.default_palette, 1);
+        assert_eq!(show.active_pipelines, 2);
+        let kinds: Vec<_> = show.pipelines.iter().map(|p| p.kind).collect();
+        assert_eq!(kinds, [PipelineKind::Background, PipelineKind::Disk]);
+        assert_eq!(show.pipelines[0].threshold, 1.5);
+        assert!(!show.pipelines[0].active);
+        assert_eq!(show.pipelines[1].shader_file, "2d_transparent.wgsl");
+        assert_eq!(show.pipelines[1].threshold, 1.8);
+        assert!(show.pipelines[1].active);
+        assert_eq!(show.pipelines[1].models[0].instances, 4);
+    }
+
+    #[test]
+    fn invalid_show() {
+        let invalid = [
+            ("colors = [\"#1b59ff\"]", "colors = []"),
+            ("default_palette = 1", "default_palette = 2"),
+            ("active_pipelines = 2", "active_pipelines = 0"),
+            ("kind = \"background\"", "kind = \"disk\""),
+            ("kind = \"disk\"", "kind = \"logo\""),
+            ("instances = 4", "instances = 0"),
+            ("[{ model = \"quad\" }]", "[]"),
+            ("model = \"disk\"", "model = \"models/none.obj\""),
+            ("2d_transparent", "none"),
+        ];
+        for (from, to) in invalid {
+            let content = SHOW.replace(from, to);
+            assert_ne!(content, SHOW, "{to}");
+            assert!(parse(&content).is_err(), "{to}");
+        }
+        assert!(matches!(
+            parse(&SHOW.replace("kind = \"disk\"", "kind = \"logo\"")),
+            Err(ShowError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse(&SHOW.replace("2d_transparent", "none")),
+            Err(ShowError::MissingAsset(_))
+        ));
+        assert!(matches!(
+            parse(&SHOW.replace("active = true", "unknown = true")),
+            Err(ShowError::Parsing(_))
+        ));
+    }
+}
@@ -0,0 +1,123 @@
+use std::iter;
+use std::num::NonZeroU32;
+use std::sync::mpsc;
+
+// Format of the offscreen frames, read back as RGBA
+const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// Bytes of a row of an offscreen frame copied to the readback buffer
+fn padded_row(width: u32) -> u32 {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    (4 * width).div_ceil(align) * align
+}
+
+// Texture read back after each frame, never resized
+pub struct Offscreen {
+    texture: wgpu::Texture,
+    buffer: wgpu::Buffer,
+    size: [u32; 2],
+}
+
+impl Offscreen {
+    pub fn new(device: &wgpu::Device, size: [u32; 2]) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen_texture"),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: OFFSCREEN_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback_buffer"),
+            size: (padded_row(size[0]) * size[1]) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Offscreen {
+            texture,
+            buffer,
+            size,
+        }
+    }
+
+    pub fn view(&self) -> wgpu::TextureView {
+        self.texture
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    // Reads back the last rendered frame, as RGBA rows
+    pub fn read(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+        let [width, height] = self.size;
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_row(width)),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(iter::once(encoder.finish()));
+
+        // Wait for the copy
+        let slice = self.buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |r| {
+            let _ = sender.send(r);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv().unwrap_or(Err(wgpu::BufferAsyncError))?;
+
+        let row = 4 * width as usize;
+        let pixels = slice
+            .get_mapped_range()
+            .chunks(padded_row(width) as usize)
+            .flat_map(|r| &r[..row])
+            .copied()
+            .collect();
+        self.buffer.unmap();
+        Ok(pixels)
+    }
+}
+
+// What a display renders to
+pub enum RenderTarget {
+    // Surface of a window
+    Surface(wgpu::Surface),
+    Offscreen(Offscreen),
+}
+
+impl RenderTarget {
+    pub fn format(&self, adapter: &wgpu::Adapter) -> wgpu::TextureFormat {
+        match self {
+            RenderTarget::Surface(surface) => surface.get_supported_formats(adapter)[0],
+            RenderTarget::Offscreen(_) => OFFSCREEN_FORMAT,
+        }
+    }
+
+    pub fn configure(&self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        if let RenderTarget::Surface(surface) = self {
+            surface.configure(device, config);
+        }
+    }
+}
@@ -25,8 +25,15 @@ pub enum Texture {
     Image(TextureInner),
 }
 
+// Single channel float texture, read with textureLoad in the shaders so without a sampler
+pub struct AudioTexture {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    pub const FRAMEBUFFER_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
 
     pub fn new_depth(
         device: &wgpu::Device,
@@ -151,7 +158,7 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            format: Self::FRAMEBUFFER_FORMAT,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
         };
         let texture = device.create_texture(&desc);
@@ -183,7 +190,7 @@ impl Texture {
     }
 
     pub fn view(&self) -> &wgpu::TextureView {
-        return &self.inner().view;
+        &self.inner().view
     }
 
     pub fn create_texture_bind_group_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
@@ -199,6 +206,19 @@ impl Texture {
         }
     }
 
+    pub fn create_audio_bind_group_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        }
+    }
+
     pub fn create_sampler_bind_group_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
         wgpu::BindGroupLayoutEntry {
             binding,
@@ -231,6 +251,55 @@ impl Texture {
     }
 }
 
+impl AudioTexture {
+    pub fn new(device: &wgpu::Device, (width, height): (u32, u32), label: &str) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let desc = wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        };
+        let texture = device.create_texture(&desc);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        AudioTexture { texture, view }
+    }
+
+    pub fn write_row(&self, queue: &wgpu::Queue, row: u32, data: &[f32]) {
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: 0, y: row, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(data),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(4 * data.len() as u32),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: data.len() as u32,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+}
+
 pub fn create_texture_image_bind_group_layout(
     nb_textures: usize,
     device: &wgpu::Device,
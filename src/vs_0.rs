@@ -1,40 +1,22 @@
 use crate::audio;
-use crate::color;
-use crate::color::{get_color, hex_to_f};
+use crate::color::get_color;
+use crate::control::{Controls, Param, Params, Trigger};
 use crate::instance::Instance;
 use crate::model::{InstanceModel, Model};
 use crate::pipeline::Pipeline;
 use crate::pipeline::{PipelineError, PipelineGroup};
+use crate::show::{ModelSource, PipelineKind, Show};
 use cgmath::Rotation3;
 use cgmath::Zero;
 use rand::prelude::*;
-use std::fmt;
-
-#[derive(clap::ValueEnum, Clone, Default, Debug, Copy)]
-pub enum Show {
-    #[default]
-    MariusJulien,
-    Lua,
-}
-
-use Show::*;
-impl fmt::Display for Show {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                MariusJulien => "marius-julien",
-                Lua => "lua",
-            }
-        )
-    }
-}
+use std::rc::Rc;
 
 const COLOR_SHADING_PERIOD: f64 = 3600.0;
 
-fn get_switch_time(time: f32, rng: &mut ThreadRng) -> f32 {
-    600.0 * rng.gen::<f32>() + 600.0 + time
+// Returns the beat of the next pipeline switch, on the first beat of a phrase 600 to 1200 s later
+fn get_switch_beat(beat_count: u32, bpm: f32, rng: &mut StdRng) -> u32 {
+    let beats = ((600.0 * rng.gen::<f32>() + 600.0) * bpm / 60.0) as u32;
+    (beat_count.saturating_add(beats) / PHRASE_BEATS + 1).saturating_mul(PHRASE_BEATS)
 }
 
 fn deactivate_pipeline(pipeline: &mut Pipeline) {
@@ -46,10 +28,16 @@ fn deactivate_pipeline(pipeline: &mut Pipeline) {
     }
 }
 
-pub const POST_SHADER_0: &str = include_str!("../shader/vs_0/post_0.wgsl");
-pub const POST_SHADER_1: &str = include_str!("../shader/vs_0/post_1.wgsl");
-const NB_DISKS: usize = 4;
+// Number of points of the disk models
+const DISK_POINTS: u32 = 200;
 const DISK_SPEED: f32 = 0.3;
+// Number of beats of a phrase
+const PHRASE_BEATS: u32 = 32;
+const LOGO_SCALE: f32 = 0.2;
+// Scale of the logo when the input is silent
+const LOGO_SILENCE_SCALE: f32 = 0.4;
+// Duration of the transitions from and to silence, in seconds
+const SILENCE_FADE_DURATION: f32 = 2.0;
 
 pub struct State {
     noise_3d_activated: bool,
@@ -64,114 +52,82 @@ pub struct State {
     wf_3d_start_time: f32,
     wf_3d_duration: f32,
     wf_3d_axis: cgmath::Vector3<f32>,
-
-    disk_activated: [bool; NB_DISKS],
-    disk_start_time: [f32; NB_DISKS],
-    disk_duration: [f32; NB_DISKS],
-    disk_scale: [f32; NB_DISKS],
-
+    wf_3d_speed: f32,
+
+    disk_activated: Vec<bool>,
+    disk_start_time: Vec<f32>,
+    disk_duration: Vec<f32>,
+    disk_scale: Vec<f32>,
+    disk_speed: Vec<f32>,
+
+    show: Rc<Show>,
+    // Pipelines of the background and of the logo
+    background: Option<usize>,
+    logo: Option<usize>,
     dyn_pipelines: Vec<usize>,
-    active_pipelines: [usize; audio::NB_AUDIO_CHANNELS],
-    pipeline_switch_time: f32,
-    show: Show,
-    rng: ThreadRng,
+    active_pipelines: Vec<usize>,
+    nb_bands: usize,
+    pipeline_switch_beat: Option<u32>,
+    beat_count: u32,
+    // From 0 (sound) to 1 (silence)
+    silence_fade: f32,
+    last_time: f32,
+    // Sampled when a pipeline is activated
+    params: Params,
+    // Offset subtracted from the activation thresholds
+    sensitivity: f32,
+    palette: usize,
+    rng: StdRng,
 }
 
 impl State {
     pub fn new(
         pipeline_group: &mut PipelineGroup,
         device: &wgpu::Device,
-        config: &wgpu::SurfaceConfiguration,
-        show: Show,
+        show: Rc<Show>,
+        nb_bands: usize,
+        // The states created with the same seed make the same random choices
+        seed: Option<u64>,
     ) -> Result<State, PipelineError> {
-        let quad = Model::new_quad(device);
-        let instance = Instance::new();
-        let instance_model = InstanceModel::new(quad, vec![instance], device);
-
-        let shader = match show {
-            Lua => include_str!("../shader/vs_0/wallpaper_noise_0.wgsl"),
-            MariusJulien => include_str!("../shader/vs_0/wallpaper_noise_1.wgsl"),
-        };
+        for p in &show.pipelines {
+            let mut instance_models = vec![];
+            for m in &p.models {
+                let model = match &m.source {
+                    ModelSource::Quad => Model::new_quad(device),
+                    ModelSource::Disk => Model::new_disk(device, DISK_POINTS),
+                    ModelSource::Obj(obj) => Model::import(obj, device)?,
+                };
+                let mut instances = vec![Instance::new(); m.instances];
+                if p.kind == PipelineKind::Logo {
+                    for i in &mut instances {
+                        i.scale = LOGO_SCALE;
+                    }
+                }
+                instance_models.push(InstanceModel::new(model, instances, device));
+            }
+            pipeline_group.add_pipeline(instance_models, &p.shader, &p.shader_file, device)?;
+        }
 
-        pipeline_group.add_pipeline(vec![instance_model], shader, device, config)?;
-
-        let quad: Model = Model::new_quad(device);
-        let mut instance = Instance::new();
-        instance.scale = 0.2;
-        let instance_model = InstanceModel::new(quad, vec![instance], device);
-
-        pipeline_group.add_pipeline(
-            vec![instance_model],
-            include_str!("../shader/vs_0/2d_logo.wgsl"),
-            device,
-            config,
-        )?;
-
-        let quad = Model::new_quad(device);
-        let instance = Instance::new();
-        let instance_model = InstanceModel::new(quad, vec![instance], device);
-
-        pipeline_group.add_pipeline(
-            vec![instance_model],
-            include_str!("../shader/vs_0/3d_noise_geometry.wgsl"),
-            device,
-            config,
-        )?;
-
-        let quad = Model::new_quad(device);
-        let q_instance = Instance::new();
-        let q_instance_model = InstanceModel::new(quad, vec![q_instance], device);
-
-        let disk = Model::new_disk(device, 200);
-        let d_instance = Instance::new();
-        let d_instance_model = InstanceModel::new(disk, vec![d_instance], device);
-
-        pipeline_group.add_pipeline(
-            vec![q_instance_model, d_instance_model],
-            include_str!("../shader/vs_0/2d_noise.wgsl"),
-            device,
-            config,
-        )?;
-
-        let disk = Model::new_disk(device, 200);
-
-        let instances = (0..NB_DISKS).map(|_| Instance::new()).collect();
-
-        let instance_model = InstanceModel::new(disk, instances, device);
-
-        pipeline_group.add_pipeline(
-            vec![instance_model],
-            include_str!("../shader/vs_0/2d_transparent.wgsl"),
-            device,
-            config,
-        )?;
-
-        let cube = Model::import(include_bytes!("../models/cube.obj"), device)?;
-        let icosphere = Model::import(include_bytes!("../models/icosphere.obj"), device)?;
-        let mf_room = Model::import(include_bytes!("../models/mfroom_3d.obj"), device)?;
-        let pyramide = Model::import(include_bytes!("../models/pyramide.obj"), device)?;
-
-        let instance = Instance::new();
-        let cube = InstanceModel::new(cube, vec![instance], device);
-        let instance = Instance::new();
-        let icosphere = InstanceModel::new(icosphere, vec![instance], device);
-        let instance = Instance::new();
-        let mf_room = InstanceModel::new(mf_room, vec![instance], device);
-        let instance = Instance::new();
-        let pyramide = InstanceModel::new(pyramide, vec![instance], device);
-
-        pipeline_group.add_pipeline(
-            vec![cube, icosphere, mf_room, pyramide],
-            include_str!("../shader/vs_0/3d.wgsl"),
-            device,
-            config,
-        )?;
-
-        let dyn_pipelines = vec![2, 3, 4, 5];
+        let position = |kind| show.pipelines.iter().position(|p| p.kind == kind);
+        let dyn_pipelines: Vec<_> = (0..show.pipelines.len())
+            .filter(|i| show.pipelines[*i].kind.trigger().is_some())
+            .collect();
         for i in &dyn_pipelines {
             deactivate_pipeline(&mut pipeline_group.pipelines[*i]);
         }
 
+        // One active pipeline per band, as long as there are enough bands, the pipelines
+        // declared active first
+        let nb_active_pipelines = nb_bands.min(show.active_pipelines);
+        let (mut active_pipelines, inactive): (Vec<_>, Vec<_>) = dyn_pipelines
+            .iter()
+            .partition(|i| show.pipelines[**i].active);
+        active_pipelines.extend(inactive);
+        active_pipelines.truncate(nb_active_pipelines);
+
+        let nb_disks =
+            position(PipelineKind::Disk).map_or(0, |i| show.pipelines[i].models[0].instances);
+
         Ok(State {
             noise_3d_activated: false,
             noise_3d_start_time: 0.0,
@@ -185,23 +141,43 @@ impl State {
             wf_3d_start_time: 0.0,
             wf_3d_duration: 0.0,
             wf_3d_axis: [0.0, 1.0, 0.0].into(),
+            wf_3d_speed: 0.0,
 
-            disk_activated: [false; NB_DISKS],
-            disk_start_time: [0.0; NB_DISKS],
-            disk_duration: [0.0; NB_DISKS],
-            disk_scale: [0.0; NB_DISKS],
+            disk_activated: vec![false; nb_disks],
+            disk_start_time: vec![0.0; nb_disks],
+            disk_duration: vec![0.0; nb_disks],
+            disk_scale: vec![0.0; nb_disks],
+            disk_speed: vec![0.0; nb_disks],
 
+            background: position(PipelineKind::Background),
+            logo: position(PipelineKind::Logo),
             dyn_pipelines,
-            active_pipelines: [3, 4, 5],
-            rng: rand::thread_rng(),
-
-            pipeline_switch_time: 0.0,
+            active_pipelines,
+            nb_bands,
+            rng: match seed {
+                Some(s) => StdRng::seed_from_u64(s),
+                None => StdRng::from_entropy(),
+            },
+
+            pipeline_switch_beat: None,
+            beat_count: 0,
+            silence_fade: 0.0,
+            last_time: 0.0,
+            params: Params::default(),
+            sensitivity: 0.0,
+            palette: show.default_palette,
             show,
         })
     }
 
+    pub fn show(&self) -> &Rc<Show> {
+        &self.show
+    }
+
     pub fn switch_pipelines(&mut self, pipelines: &mut [Pipeline]) {
-        let i = (0..audio::NB_AUDIO_CHANNELS).choose(&mut self.rng).unwrap();
+        let i = (0..self.active_pipelines.len())
+            .choose(&mut self.rng)
+            .unwrap();
         let old_index = self.active_pipelines[i];
         deactivate_pipeline(&mut pipelines[old_index]);
         let mut candidate_pipelines = vec![];
@@ -220,33 +196,163 @@ impl State {
         let new_index = candidate_pipelines.choose(&mut self.rng).unwrap();
         self.active_pipelines[i] = *new_index;
     }
+
+    // Dynamic pipeline of the trigger, if the show has one
+    fn pipeline_index(&self, trigger: Trigger) -> Option<usize> {
+        self.show
+            .pipelines
+            .iter()
+            .position(|p| p.kind.trigger() == Some(trigger))
+    }
+
+    // Activates a pipeline now, replacing an active pipeline if needed
+    pub fn trigger(&mut self, pipelines: &mut [Pipeline], time: f32, trigger: Trigger) {
+        let Some(index) = self.pipeline_index(trigger) else {
+            return;
+        };
+        if !self.active_pipelines.contains(&index) {
+            let i = (0..self.active_pipelines.len())
+                .choose(&mut self.rng)
+                .unwrap();
+            deactivate_pipeline(&mut pipelines[self.active_pipelines[i]]);
+            self.active_pipelines[i] = index;
+        }
+        self.activate(pipelines, time, index);
+    }
+
+    // Puts a dynamic pipeline into the slot of a band, swapping it with the slot holding it
+    pub fn force(&mut self, pipelines: &mut [Pipeline], slot: usize, trigger: Trigger) {
+        let Some(index) = self.pipeline_index(trigger) else {
+            return;
+        };
+        match self.active_pipelines.iter().position(|a| *a == index) {
+            Some(i) => self.active_pipelines.swap(slot, i),
+            None => {
+                deactivate_pipeline(&mut pipelines[self.active_pipelines[slot]]);
+                self.active_pipelines[slot] = index;
+            }
+        }
+    }
+
+    fn activate(&mut self, pipelines: &mut [Pipeline], time: f32, index: usize) {
+        let i_ms = &mut pipelines[index].instance_models;
+        match self.show.pipelines[index].kind {
+            PipelineKind::Noise3d => self.activate_noise_3d(time, i_ms),
+            PipelineKind::Full => self.activate_full(time, i_ms),
+            PipelineKind::Disk => self.activate_disk(time, &mut i_ms[0].instances),
+            PipelineKind::Wf3d => self.activate_wf_3d(time, i_ms),
+            PipelineKind::Background | PipelineKind::Logo => unreachable!(),
+        }
+    }
+
     pub fn update(
         &mut self,
         pipelines: &mut [Pipeline],
         time: f32,
         old_audio: &audio::Data,
-        new_audio: &audio::Data,
+        new_audio: &[audio::Data],
+        controls: &Controls,
     ) {
-        if time > self.pipeline_switch_time
-            && self.dyn_pipelines.len() > self.active_pipelines.len()
-        {
-            self.pipeline_switch_time = get_switch_time(time, &mut self.rng);
+        self.params = controls.params;
+        self.sensitivity = controls.sensitivity;
+        let nb_palettes = self.show.palettes.len();
+        match controls.palette {
+            Some(p) if p < nb_palettes => self.palette = p,
+            Some(p) => log::warn!("The show {} has no palette {p}", self.show.name),
+            None => {}
+        }
+        if controls.next_palette {
+            self.palette = (self.palette + 1) % nb_palettes;
+        }
+
+        let slot = controls.band % self.active_pipelines.len();
+        if let Some(t) = controls.force {
+            self.force(pipelines, slot, t);
+        }
+        if controls.logo_only {
+            for a in &self.active_pipelines {
+                deactivate_pipeline(&mut pipelines[*a]);
+            }
+        } else {
+            for t in &controls.triggers {
+                self.trigger(pipelines, time, *t);
+            }
+            if controls.activate {
+                self.activate(pipelines, time, self.active_pipelines[slot]);
+            }
+        }
+
+        // The beat count restarts with the external clock
+        let audio = new_audio.last().unwrap_or(old_audio);
+        let restarted = audio.beat_count < self.beat_count;
+        self.beat_count = audio.beat_count;
+        let switch = restarted
+            || self
+                .pipeline_switch_beat
+                .is_none_or(|b| audio.beat_count >= b);
+        // The tempo is only known once the audio has been analysed
+        if switch && audio.bpm > 0.0 && self.dyn_pipelines.len() > self.active_pipelines.len() {
+            self.pipeline_switch_beat =
+                Some(get_switch_beat(audio.beat_count, audio.bpm, &mut self.rng));
             self.switch_pipelines(pipelines);
         }
 
-        for (i, a) in self.active_pipelines.clone().iter().enumerate() {
-            let o_a = old_audio.gain[i];
-            let n_a = new_audio.gain[i];
-            match a {
-                2 => self.update_noise_3d(&mut pipelines[*a], time, o_a, n_a),
-                3 => self.update_full(&mut pipelines[*a], time, o_a, n_a),
-                4 => self.update_disk(&mut pipelines[*a], time, o_a, n_a),
-                5 => self.update_wf_3d(&mut pipelines[*a], time, o_a, n_a),
-                _ => unreachable!(),
+        // Each frame is compared with the previous one so that no threshold crossing is missed
+        let mut gains = vec![old_audio.gain.as_slice()];
+        gains.extend(new_audio.iter().map(|d| d.gain.as_slice()));
+        if gains.len() == 1 {
+            gains.push(old_audio.gain.as_slice());
+        }
+
+        let nb_active_pipelines = self.active_pipelines.len();
+        let active_pipelines = if controls.logo_only {
+            vec![]
+        } else {
+            self.active_pipelines.clone()
+        };
+        for (i, a) in active_pipelines.iter().enumerate() {
+            // The bands are spread over the active pipelines
+            let band = i * self.nb_bands / nb_active_pipelines;
+            let description = &self.show.pipelines[*a];
+            let (kind, threshold) = (description.kind, description.threshold - self.sensitivity);
+            let pipeline = &mut pipelines[*a];
+            for g in gains.windows(2) {
+                let o_a = g[0][band];
+                let n_a = g[1][band];
+                match kind {
+                    PipelineKind::Noise3d => {
+                        self.update_noise_3d(pipeline, time, threshold, o_a, n_a)
+                    }
+                    PipelineKind::Full => self.update_full(pipeline, time, threshold, o_a, n_a),
+                    PipelineKind::Disk => self.update_disk(pipeline, time, threshold, o_a, n_a),
+                    PipelineKind::Wf3d => self.update_wf_3d(pipeline, time, threshold, o_a, n_a),
+                    PipelineKind::Background | PipelineKind::Logo => unreachable!(),
+                }
             }
         }
 
-        self.update_background_color(&mut pipelines[0], time);
+        if let Some(i) = self.logo {
+            self.update_logo(&mut pipelines[i], time, audio.silence || controls.logo_only);
+        }
+        if let Some(i) = self.background {
+            self.update_background_color(&mut pipelines[i], time);
+        }
+    }
+
+    // The logo grows while the input is silent or only the logo is displayed
+    fn update_logo(&mut self, pipeline: &mut Pipeline, time: f32, silence: bool) {
+        let step = (time - self.last_time) / SILENCE_FADE_DURATION;
+        self.last_time = time;
+        self.silence_fade = if silence {
+            (self.silence_fade + step).min(1.0)
+        } else {
+            (self.silence_fade - step).max(0.0)
+        };
+
+        // Smoothstep
+        let x = self.silence_fade * self.silence_fade * (3.0 - 2.0 * self.silence_fade);
+        pipeline.instance_models[0].instances[0].scale =
+            LOGO_SCALE + (LOGO_SILENCE_SCALE - LOGO_SCALE) * x;
     }
 
     fn update_background_color(&self, pipeline: &mut Pipeline, time: f32) {
@@ -255,17 +361,9 @@ impl State {
         let t: f64 = 2.0 * pi * time as f64 / COLOR_SHADING_PERIOD;
         let x = t.cos() as f32;
 
+        let [c_0, c_1] = self.show.palettes[self.palette].background;
         for i in 0..4 {
-            match self.show {
-                Show::Lua => {
-                    bg.color[i] = hex_to_f(color::COLOR_0_0[i]) * x
-                        + (1.0 - x) * hex_to_f(color::COLOR_4_0[i])
-                }
-                Show::MariusJulien => {
-                    bg.color[i] = hex_to_f(color::COLOR_2_1[i]) * x
-                        + (1.0 - x) * hex_to_f(color::COLOR_4_1[i])
-                }
-            }
+            bg.color[i] = c_0.0[i] * x + (1.0 - x) * c_1.0[i];
         }
     }
 
@@ -273,10 +371,11 @@ impl State {
         &mut self,
         pipeline: &mut Pipeline,
         time: f32,
+        threshold: f32,
         old_audio: f32,
         new_audio: f32,
     ) {
-        if new_audio > 1.5 && old_audio < 1.5 {
+        if new_audio > threshold && old_audio < threshold {
             self.activate_noise_3d(time, &mut pipeline.instance_models);
         }
 
@@ -289,8 +388,15 @@ impl State {
         }
     }
 
-    fn update_full(&mut self, pipeline: &mut Pipeline, time: f32, old_audio: f32, new_audio: f32) {
-        if new_audio > 1.5 && old_audio < 1.5 {
+    fn update_full(
+        &mut self,
+        pipeline: &mut Pipeline,
+        time: f32,
+        threshold: f32,
+        old_audio: f32,
+        new_audio: f32,
+    ) {
+        if new_audio > threshold && old_audio < threshold {
             self.activate_full(time, &mut pipeline.instance_models);
         }
 
@@ -303,14 +409,24 @@ impl State {
         }
     }
 
-    fn update_wf_3d(&mut self, pipeline: &mut Pipeline, time: f32, old_audio: f32, new_audio: f32) {
-        if new_audio > 2.0 && old_audio < 2.0 {
+    fn update_wf_3d(
+        &mut self,
+        pipeline: &mut Pipeline,
+        time: f32,
+        threshold: f32,
+        old_audio: f32,
+        new_audio: f32,
+    ) {
+        if new_audio > threshold && old_audio < threshold {
             self.activate_wf_3d(time, &mut pipeline.instance_models);
         }
 
         if self.wf_3d_activated.0 {
             let i = &mut pipeline.instance_models[self.wf_3d_activated.1].instances[0];
-            i.rotation = cgmath::Basis3::from_axis_angle(self.wf_3d_axis, cgmath::Rad(0.5 * time));
+            i.rotation = cgmath::Basis3::from_axis_angle(
+                self.wf_3d_axis,
+                cgmath::Rad(self.wf_3d_speed * time),
+            );
             let t = time - self.wf_3d_start_time;
             if t > self.wf_3d_duration {
                 self.wf_3d_activated.0 = false;
@@ -323,7 +439,9 @@ impl State {
         let i = (0..i_ms.len()).choose(&mut self.rng).unwrap();
         self.wf_3d_activated = (true, i);
         self.wf_3d_start_time = time;
-        self.wf_3d_duration = 3.0 * self.rng.gen::<f32>() + 3.0;
+        self.wf_3d_duration =
+            (3.0 * self.rng.gen::<f32>() + 3.0) * self.params.multiplier(Param::Duration);
+        self.wf_3d_speed = 0.5 * self.params.multiplier(Param::Speed);
         self.wf_3d_axis = {
             let mut axis = cgmath::Vector3::<f32>::zero();
             while axis == cgmath::Vector3::<f32>::zero() {
@@ -343,7 +461,7 @@ impl State {
 
         let instance = &mut i_ms[i].instances[0];
 
-        instance.color = get_color(&mut self.rng, self.show);
+        instance.color = get_color(&mut self.rng, &self.show.palettes[self.palette].colors);
         instance.scale = 1.0;
         instance.position = (
             0.5 - 1.0 * self.rng.gen::<f32>(),
@@ -356,11 +474,12 @@ impl State {
     fn activate_noise_3d(&mut self, time: f32, i_ms: &mut [InstanceModel]) {
         self.noise_3d_activated = true;
         self.noise_3d_start_time = time;
-        self.noise_3d_duration = 1.0 * self.rng.gen::<f32>() + 1.0;
+        self.noise_3d_duration =
+            (1.0 * self.rng.gen::<f32>() + 1.0) * self.params.multiplier(Param::Duration);
 
         let instance = &mut i_ms[0].instances[0];
 
-        instance.color = get_color(&mut self.rng, self.show);
+        instance.color = get_color(&mut self.rng, &self.show.palettes[self.palette].colors);
         instance.scale = 1.0;
         instance.position[0] = 1.0;
         instance.position[1] = 0.5 - 1.0 * self.rng.gen::<f32>();
@@ -371,14 +490,15 @@ impl State {
         let i = (0..i_ms.len()).choose(&mut self.rng).unwrap();
         self.full_activated = (true, i);
         self.full_start_time = time;
-        self.full_duration = 0.6 * self.rng.gen::<f32>() + 0.4;
+        self.full_duration =
+            (0.6 * self.rng.gen::<f32>() + 0.4) * self.params.multiplier(Param::Duration);
 
         for i_m in &mut *i_ms {
             i_m.instances[0].scale = 0.0;
         }
 
         let instance = &mut i_ms[i].instances[0];
-        instance.color = get_color(&mut self.rng, self.show);
+        instance.color = get_color(&mut self.rng, &self.show.palettes[self.palette].colors);
         instance.scale = self.rng.gen::<f32>() * 0.1 + 0.1;
         instance.position = (
             0.5 - 1.0 * self.rng.gen::<f32>(),
@@ -388,13 +508,20 @@ impl State {
             .into();
     }
 
-    fn update_disk(&mut self, pipeline: &mut Pipeline, time: f32, old_audio: f32, new_audio: f32) {
+    fn update_disk(
+        &mut self,
+        pipeline: &mut Pipeline,
+        time: f32,
+        threshold: f32,
+        old_audio: f32,
+        new_audio: f32,
+    ) {
         let disks_i = &mut pipeline.instance_models[0].instances;
 
-        if new_audio > 1.5 && old_audio < 1.5 {
+        if new_audio > threshold && old_audio < threshold {
             self.activate_disk(time, disks_i);
         }
-        for (i, d) in disks_i.iter_mut().enumerate().take(NB_DISKS) {
+        for (i, d) in disks_i.iter_mut().enumerate() {
             if self.disk_activated[i] {
                 let t = time - self.disk_start_time[i];
                 if t > self.disk_duration[i] {
@@ -402,17 +529,17 @@ impl State {
                     d.scale = 0.0;
                     continue;
                 }
-                d.scale = self.disk_scale[i] + DISK_SPEED * t;
+                d.scale = self.disk_scale[i] + self.disk_speed[i] * t;
             }
         }
     }
 
     fn activate_disk(&mut self, time: f32, instances: &mut [Instance]) {
-        for (i, d) in instances.iter_mut().enumerate().take(NB_DISKS) {
+        for (i, d) in instances.iter_mut().enumerate() {
             if !self.disk_activated[i] {
                 self.disk_activated[i] = true;
 
-                d.color = get_color(&mut self.rng, self.show);
+                d.color = get_color(&mut self.rng, &self.show.palettes[self.palette].colors);
                 d.position = (
                     1.0 - 2.0 * self.rng.gen::<f32>(),
                     1.0 - 2.0 * self.rng.gen::<f32>(),
@@ -421,7 +548,9 @@ impl State {
                     .into();
                 self.disk_start_time[i] = time;
                 self.disk_scale[i] = 0.1;
-                self.disk_duration[i] = self.rng.gen::<f32>() + 0.5;
+                self.disk_duration[i] =
+                    (self.rng.gen::<f32>() + 0.5) * self.params.multiplier(Param::Duration);
+                self.disk_speed[i] = DISK_SPEED * self.params.multiplier(Param::Speed);
                 return;
             }
         }
@@ -0,0 +1,65 @@
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+// Watches the WGSL shaders of a directory
+pub struct ShaderWatcher {
+    // The directory is watched as long as the watcher lives
+    _watcher: RecommendedWatcher,
+    receiver: mpsc::Receiver<PathBuf>,
+}
+
+impl ShaderWatcher {
+    pub fn new(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                match event {
+                    // Editors often save a file by replacing it
+                    Ok(e) if matches!(e.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                        for p in e.paths {
+                            let _ = sender.send(p);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Failed to watch the shaders: {e}"),
+                }
+            })?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| {
+                format!(
+                    "Failed to watch the shader directory {}: {e}",
+                    dir.display()
+                )
+            })?;
+        Ok(ShaderWatcher {
+            _watcher: watcher,
+            receiver,
+        })
+    }
+
+    // Returns the file names and the sources of the shaders modified since the previous call
+    pub fn changes(&self) -> Vec<(String, String)> {
+        let paths: BTreeSet<_> = self
+            .receiver
+            .try_iter()
+            .filter(|p| p.extension().is_some_and(|e| e == "wgsl"))
+            .collect();
+        paths
+            .into_iter()
+            .filter_map(|p| {
+                let file = p.file_name()?.to_string_lossy().into_owned();
+                match fs_err::read_to_string(&p) {
+                    Ok(source) => Some((file, source)),
+                    Err(e) => {
+                        log::warn!("{e}");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+}
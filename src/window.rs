@@ -0,0 +1,123 @@
+use serde::Deserialize;
+use std::error::Error;
+use std::str::FromStr;
+use winit::dpi::PhysicalSize;
+use winit::event_loop::EventLoop;
+use winit::monitor::MonitorHandle;
+use winit::window::{Fullscreen, Window, WindowBuilder};
+
+// Inner size of a window, in physical pixels
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(try_from = "String")]
+pub struct WindowSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for WindowSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let size = s
+            .split_once('x')
+            .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)));
+        match size {
+            Some((width, height)) if width > 0 && height > 0 => Ok(WindowSize { width, height }),
+            _ => Err(format!("invalid window size {s} (expected WIDTHxHEIGHT)")),
+        }
+    }
+}
+
+impl TryFrom<String> for WindowSize {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct WindowConfig {
+    // Id or part of the name of the monitor, the primary monitor by default
+    pub monitor: Option<String>,
+    pub fullscreen: bool,
+    pub size: Option<WindowSize>,
+    // Window without title bar and borders
+    pub borderless: bool,
+    pub hide_cursor: bool,
+}
+
+fn select_monitor(
+    event_loop: &EventLoop<()>,
+    selector: &str,
+) -> Result<MonitorHandle, Box<dyn Error>> {
+    let monitors = event_loop.available_monitors();
+    if let Ok(id) = selector.parse::<usize>() {
+        return monitors
+            .into_iter()
+            .nth(id)
+            .ok_or_else(|| format!("No monitor with id {}", id).into());
+    }
+
+    let pattern = selector.to_lowercase();
+    for m in monitors {
+        if m.name()
+            .is_some_and(|n| n.to_lowercase().contains(&pattern))
+        {
+            return Ok(m);
+        }
+    }
+    Err(format!("No monitor matching \"{}\"", selector).into())
+}
+
+pub fn list_monitors() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::new();
+    let primary = event_loop.primary_monitor();
+    for (i, m) in event_loop.available_monitors().enumerate() {
+        let size = m.size();
+        let position = m.position();
+        println!(
+            "[MONITOR {}] {} ({}x{} at {},{}){}",
+            i,
+            m.name().unwrap_or_default(),
+            size.width,
+            size.height,
+            position.x,
+            position.y,
+            if primary.as_ref() == Some(&m) {
+                " primary"
+            } else {
+                ""
+            }
+        );
+    }
+    Ok(())
+}
+
+pub fn build_window(
+    event_loop: &EventLoop<()>,
+    config: &WindowConfig,
+) -> Result<Window, Box<dyn Error>> {
+    let monitor = match &config.monitor {
+        Some(selector) => Some(select_monitor(event_loop, selector)?),
+        None => event_loop.primary_monitor(),
+    };
+
+    let mut builder = WindowBuilder::new().with_decorations(!config.borderless);
+    if let Some(s) = config.size {
+        builder = builder.with_inner_size(PhysicalSize::new(s.width, s.height));
+    }
+    if config.fullscreen {
+        builder = builder.with_fullscreen(Some(Fullscreen::Borderless(monitor)));
+    } else if let Some(m) = monitor.filter(|_| config.monitor.is_some()) {
+        // The window is placed in the top left corner of the selected monitor
+        builder = builder.with_position(m.position());
+    }
+
+    let window = builder.build(event_loop)?;
+    if config.hide_cursor {
+        window.set_cursor_visible(false);
+    }
+    Ok(window)
+}